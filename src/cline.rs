@@ -1,43 +1,179 @@
-use crate::{mconf, mvers, temp};
-use clap::{Parser, Subcommand};
-use flate2::read::GzEncoder;
+use crate::errors::{self, McwrError};
+use crate::mconf::Scope;
+use crate::mcrash::{CrashReport, CrashWatch};
+use crate::mjava::Damage;
+use crate::mfeat::{Features, QuickPlay, Resolution};
+use crate::mvers::{DownloadCallback, DownloadEvent, LaunchOptions, ManifestEntry, OutputLine, Version};
+use crate::{mconf, mcrash, mdirs, mjava, mlog4j, mlogs, mproc, mprogress, mvers, temp};
+#[cfg(target_os = "linux")]
+use crate::mdesk;
+use clap::error::ErrorKind;
+use clap::{CommandFactory, Parser, Subcommand, ValueEnum};
+use dwldutil::Downloader;
+use flate2::write::GzEncoder;
 use flate2::Compression;
-use mcd::api::ApiClientUtil;
+use std::collections::HashSet;
+use std::env;
 use std::fs::{self, File};
 use std::io;
 use std::io::Write;
-use std::path::Path;
-use std::process::exit;
+use std::path::{Path, PathBuf};
+use std::process::{self, exit};
+use std::sync::Arc;
+use std::time::{Duration, UNIX_EPOCH};
 use tar::Builder;
-use anyhow::{Ok, Result};
+use log::{trace, warn};
+use serde::Serialize;
+
+/// Documented in `--help`, the codes are defined in `errors`
+const EXIT_CODES: &str = "\
+Exit codes:
+  1  general failure
+  2  invalid arguments
+  3  invalid configuration
+  4  version manifest or unknown version
+  5  download failure
+  6  missing or broken version metadata
+  7  the game cannot be launched
+  8  export failure
+  `run` exits with the exit code of the game";
 
 #[derive(Parser, Debug)]
-#[command(author = "kristian/k3nder", version = "0.3.0", about)]
+#[command(author = "kristian/k3nder", version = "0.3.0", about, after_help = EXIT_CODES)]
 struct Args {
+    /// Keep everything in this directory instead of the XDG directories, `MCWR_HOME` does the same
+    #[arg(long, global = true, value_name = "DIR")]
+    root: Option<PathBuf>,
+    /// Format of the output of ls, find, info, verify and download
+    #[arg(long, global = true, value_enum, default_value_t = Output::Text)]
+    output: Output,
     #[command(subcommand)]
     command: Commands,
 }
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum LsSort {
+    Name,
+    Size,
+    Played,
+    Playtime,
+    Java,
+}
+#[derive(Debug, Clone)]
+enum LsFilter {
+    Name(String),
+    Loader(String),
+    Java(usize),
+    Type(String),
+}
+impl LsFilter {
+    fn matches(&self, version: &Version) -> bool {
+        match self {
+            LsFilter::Name(name) => version.version.contains(name.as_str()),
+            LsFilter::Loader(loader) => version.loader() == loader,
+            LsFilter::Java(major) => version.java_version == *major,
+            LsFilter::Type(version_type) => version.version_type == *version_type,
+        }
+    }
+}
+fn parse_filter(filter: &str) -> Result<LsFilter, String> {
+    let Some((key, value)) = filter.split_once('=') else {
+        return Ok(LsFilter::Name(filter.to_owned()));
+    };
+    match key {
+        "loader" => Ok(LsFilter::Loader(value.to_owned())),
+        "java" => value.parse().map(LsFilter::Java).map_err(|e| format!("invalid Java version `{}`: {}", value, e)),
+        "type" => Ok(LsFilter::Type(value.to_owned())),
+        _ => Err(format!("unknown filter `{}`, use loader, java or type", key)),
+    }
+}
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum Output {
+    Text,
+    /// JSON documents, the download progress as one JSON event per line
+    Json,
+}
 #[derive(Debug, Subcommand)]
 enum Commands {
+    /// Download one or more versions, the files they share are downloaded once
+    #[command(mut_group("RunArgs", |group| group.requires("run")))]
     Download {
-        #[arg()]
-        version: String,
+        /// Versions of the manifest, or paths of client JSONs
+        #[arg(required_unless_present = "from_file")]
+        versions: Vec<String>,
+        /// Download the versions listed in this file as well, one per line, `#` starts a comment
+        #[arg(long, value_name = "FILE")]
+        from_file: Option<PathBuf>,
+        /// Run the version once it is downloaded, only with a single version
         #[arg(short = 'R')]
         run: bool,
+        /// Hide the download progress and the output of the game
         #[arg(short = 'S')]
         silent: bool,
         #[arg(short = 'A')]
         no_assets: bool,
+        #[command(flatten)]
+        options: RunArgs,
     },
     Run {
         #[arg()]
         version: String,
         #[arg(short = 'S')]
         silent: bool,
+        /// Start the game in the background and return immediately
+        #[arg(short = 'D', long)]
+        detach: bool,
+        /// Print the java command line instead of running it
+        #[arg(long)]
+        print_command: bool,
+        #[command(flatten)]
+        options: RunArgs,
+    },
+    /// Write a shell script that launches the version without mcwr
+    Script {
+        #[arg()]
+        version: String,
+        /// Path of the script, `<version>.sh` by default
+        #[arg(short = 'o', long)]
+        path: Option<String>,
+    },
+    /// Manage the desktop entries of the versions
+    #[cfg(target_os = "linux")]
+    Desktop {
+        #[command(subcommand)]
+        command: DesktopCommands,
+    },
+    /// Manage the Java runtimes downloaded by mcwr
+    Java {
+        #[command(subcommand)]
+        command: JavaCommands,
+    },
+    /// Read and change the configuration
+    Config {
+        /// Use the overrides of this version, `mcwr.toml` in its home, over the global configuration
+        #[arg(long, value_name = "ID")]
+        version: Option<String>,
+        #[command(subcommand)]
+        command: ConfigCommands,
+    },
+    /// List the running games
+    Ps,
+    /// Stop a running game, by version or pid
+    Kill {
+        #[arg()]
+        target: String,
+        /// Seconds to wait after SIGTERM before sending SIGKILL
+        #[arg(short = 't', long, default_value_t = 10)]
+        timeout: u64,
     },
     Ls {
         #[arg(short = 'S')]
         short: bool,
+        /// Order of the versions, the sizes and times are listed from the largest
+        #[arg(long, value_enum, default_value_t = LsSort::Name)]
+        sort: LsSort,
+        /// Show only the versions matching `loader=`, `java=` or `type=`, or whose name contains the value
+        #[arg(long, value_name = "[KEY=]VALUE", value_parser = parse_filter)]
+        filter: Vec<LsFilter>,
     },
     Remove {
         #[arg()]
@@ -49,6 +185,44 @@ enum Commands {
         #[arg()]
         version: String,
     },
+    /// Show the metadata of an installed version
+    Info {
+        #[arg()]
+        version: String,
+    },
+    /// Check the files of the installed versions against their hashes
+    Verify {
+        /// Version to check, every installed version by default
+        #[arg()]
+        version: Option<String>,
+    },
+    /// Copy an installed version under a new name, to experiment without downloading it again
+    Clone {
+        #[arg()]
+        version: String,
+        #[arg()]
+        to: String,
+    },
+    /// Rename an installed version
+    Rename {
+        #[arg()]
+        version: String,
+        #[arg()]
+        to: String,
+    },
+    Crashes {
+        #[arg()]
+        version: String,
+    },
+    Logs {
+        #[arg()]
+        version: String,
+        #[arg(short = 'f', long)]
+        follow: bool,
+        /// Session to show, 1 is the most recent
+        #[arg(short = 's', long, default_value_t = 1)]
+        session: usize,
+    },
     #[cfg(feature = "export")]
     Export {
         #[arg()]
@@ -56,104 +230,809 @@ enum Commands {
     },
 }
 
-pub fn run() -> Result<()> {
+#[cfg(target_os = "linux")]
+#[derive(Debug, Subcommand)]
+enum DesktopCommands {
+    /// Add a desktop entry running the version to the applications menu
+    Install {
+        #[arg()]
+        version: String,
+    },
+    /// Remove the desktop entry of the version
+    Rm {
+        #[arg()]
+        version: String,
+    },
+}
+
+#[derive(Debug, Subcommand)]
+enum ConfigCommands {
+    /// Print the effective value of a key, the keys of tables are `table.key`
+    Get {
+        #[arg()]
+        key: String,
+        /// Print where the value comes from: file, env or default
+        #[arg(long)]
+        show_origin: bool,
+    },
+    /// Write a key to the configuration file
+    Set {
+        #[arg()]
+        key: String,
+        #[arg(allow_hyphen_values = true)]
+        value: String,
+    },
+    /// Remove a key from the configuration file, going back to its default
+    Unset {
+        #[arg()]
+        key: String,
+    },
+    /// Print every effective value and where it comes from
+    List,
+    /// Open the configuration file in $VISUAL or $EDITOR
+    Edit,
+    /// Print the path of the configuration file
+    Path,
+}
+
+#[derive(Debug, Subcommand)]
+enum JavaCommands {
+    /// List the downloaded runtimes and the versions using them
+    Ls {
+        /// List the Java installations of the system instead
+        #[arg(long)]
+        system: bool,
+    },
+    /// Download the runtime of a Java major version
+    Install {
+        #[arg()]
+        major: usize,
+    },
+    /// Remove a runtime, by id or major version
+    Rm {
+        #[arg()]
+        runtime: String,
+        #[arg(short = 'C')]
+        confirm: bool,
+    },
+    /// Check the files of the runtimes against the hashes recorded on install
+    Verify {
+        /// Runtime to check, by id or major version, all by default
+        #[arg()]
+        runtime: Option<String>,
+    },
+}
+
+/// Launch options of the commands that run a version
+#[derive(Debug, clap::Args)]
+struct RunArgs {
+    /// Maximum heap, e.g. `4G`, or `MIN:MAX` to set the initial heap too
+    #[arg(short = 'm', long)]
+    memory: Option<String>,
+    /// Extra JVM argument, can be repeated
+    #[arg(long = "jvm-arg", allow_hyphen_values = true)]
+    jvm_args: Vec<String>,
+    /// Extra game argument, can be repeated
+    #[arg(long = "game-arg", allow_hyphen_values = true)]
+    game_args: Vec<String>,
+    /// JVM flags preset from the `presets` configuration
+    #[arg(long)]
+    preset: Option<String>,
+    /// Java home or binary to run with instead of the runtime of the version,
+    /// `auto` picks an installed Java matching the version
+    #[arg(long, value_name = "PATH|auto")]
+    java: Option<String>,
+    /// Join a multiplayer server when the game starts, `host[:port]`
+    #[arg(long, group = "quick_play")]
+    server: Option<String>,
+    /// Open a singleplayer world when the game starts
+    #[arg(long, group = "quick_play")]
+    world: Option<String>,
+    /// Join a realm when the game starts
+    #[arg(long, group = "quick_play")]
+    realm: Option<String>,
+    /// Width of the game window
+    #[arg(long, requires = "height")]
+    width: Option<u32>,
+    /// Height of the game window
+    #[arg(long, requires = "width")]
+    height: Option<u32>,
+    /// Start the game in fullscreen
+    #[arg(long, overrides_with = "no_fullscreen")]
+    fullscreen: bool,
+    /// Start the game in a window, over a saved `--fullscreen`
+    #[arg(long)]
+    no_fullscreen: bool,
+    /// Play the demo
+    #[arg(long, overrides_with = "no_demo")]
+    demo: bool,
+    /// Play the full game, over a saved `--demo`
+    #[arg(long)]
+    no_demo: bool,
+    /// Save these options as the defaults of the version
+    #[arg(long)]
+    save: bool,
+    /// Clear the saved defaults of the version, before saving the new ones with `--save`
+    #[arg(long)]
+    reset_defaults: bool,
+}
+impl RunArgs {
+    fn options(&self) -> LaunchOptions {
+        LaunchOptions {
+            memory: self.memory.clone(),
+            preset: self.preset.clone(),
+            java: self.java.clone(),
+            jvm_args: self.jvm_args.clone(),
+            game_args: self.game_args.clone(),
+            features: Features {
+                quick_play: self.quick_play(),
+                resolution: self
+                    .width
+                    .zip(self.height)
+                    .map(|(width, height)| Resolution { width, height }),
+                fullscreen: toggle(self.fullscreen, self.no_fullscreen),
+                demo: toggle(self.demo, self.no_demo),
+            },
+        }
+    }
+    fn quick_play(&self) -> Option<QuickPlay> {
+        if let Some(server) = &self.server {
+            return Some(QuickPlay::Server(server.clone()));
+        }
+        if let Some(world) = &self.world {
+            return Some(QuickPlay::World(world.clone()));
+        }
+        self.realm.clone().map(QuickPlay::Realm)
+    }
+}
+
+/// Value of a feature given with `--feature` or `--no-feature`, the last one wins
+fn toggle(on: bool, off: bool) -> Option<bool> {
+    match (on, off) {
+        (true, _) => Some(true),
+        (_, true) => Some(false),
+        _ => None,
+    }
+}
+
+pub fn run() -> Result<(), McwrError> {
     let args = Args::parse();
+    let output = args.output;
+    crate::init(args.root.clone(), !matches!(args.command, Commands::Config { .. }))?;
 
     match args.command {
         Commands::Download {
-            version,
+            versions,
+            from_file,
             run,
             silent,
             no_assets,
+            options,
         } => {
-            let apic = ApiClientUtil::new(&mconf::get::<String>("manifest"))?;
-
-            let client = if version.starts_with("./") {
-                apic.load(version.as_str(), temp!("mcwr-client.tmp"))?
-            } else {
-                apic.fetch(&version, temp!("mcwr-client.tmp"))?
+            let versions = download_list(versions, from_file.as_deref())?;
+            if versions.is_empty() {
+                Args::command().error(ErrorKind::MissingRequiredArgument, "no versions to download").exit();
+            }
+            if run && versions.len() > 1 {
+                Args::command().error(ErrorKind::ArgumentConflict, "-R runs a single version").exit();
+            }
+            let on_event: DownloadCallback = match output {
+                Output::Text if silent => Arc::new(|_| {}),
+                Output::Text => mprogress::bars(),
+                Output::Json => Arc::new(|event| print_event(&event)),
             };
-            mvers::download(&client, !no_assets)?;
+            // the versions that cannot be fetched are reported with the others
+            let mut clients = Vec::new();
+            let mut fetched = Vec::new();
+            for (i, version) in versions.iter().enumerate() {
+                let tmp = temp!(format!("mcwr-client-{}.tmp", i));
+                match mvers::fetch(version, &tmp) {
+                    Ok(client) => {
+                        clients.push((client, PathBuf::from(tmp)));
+                        fetched.push(Ok(()));
+                    }
+                    Err(e) => {
+                        on_event(DownloadEvent::Failed { version: version.clone(), error: errors::chain(&e) });
+                        fetched.push(Err(e));
+                    }
+                }
+            }
+            let mut installed = mvers::download_all(&clients, !no_assets, on_event).into_iter();
+            let mut results: Vec<(String, Result<(), McwrError>)> = versions
+                .into_iter()
+                .zip(fetched)
+                .map(|(version, fetched)| match fetched {
+                    Ok(()) => installed
+                        .next()
+                        .map_or((version, Ok(())), |(id, result)| (id, result.map_err(McwrError::from))),
+                    Err(e) => (version, Err(e)),
+                })
+                .collect();
+            // a single version fails as before, without summary
+            if results.len() == 1 {
+                let (version, result) = results.remove(0);
+                result?;
+                if run {
+                    let mut vers = mvers::get(version)?;
+                    let options = launch_options(&mut vers, &options)?;
+                    exit(launch(&vers, &options, silent)?);
+                }
+                return Ok(());
+            }
+            if output == Output::Text {
+                for (version, result) in &results {
+                    match result {
+                        Ok(()) => println!("{}\tinstalled", version),
+                        Err(e) => println!("{}\tfailed: {}", version, errors::chain(e)),
+                    }
+                }
+            }
+            if let Some((_, Err(e))) = results.iter().find(|(_, result)| result.is_err()) {
+                exit(e.exit_code());
+            }
+        }
+        Commands::Run { version, silent, detach, print_command, options } => {
+            let mut vers = mvers::get(version)?;
+            let launch_options = launch_options(&mut vers, &options)?;
+            if print_command {
+                println!("{}", shell_command(&vers.command(&launch_options)?));
+                return Ok(());
+            }
+            if detach {
+                let pid = vers.launch_detached(&launch_options)?;
+                println!("{} started with pid {}", vers.version, pid);
+                return Ok(());
+            }
+            exit(launch(&vers, &launch_options, silent)?);
+        }
+        Commands::Script { version, path } => {
+            let vers = mvers::get(version)?;
+            let output = path.unwrap_or_else(|| format!("{}.sh", vers.version));
+            let dir = &mdirs::get().data;
+            let script = format!(
+                "#!/bin/sh\n# Launches {}, generated by mcwr\ncd {} || exit 1\nexec {} \"$@\"\n",
+                vers.version,
+                shell_quote(&dir.to_string_lossy()),
+                shell_command(&vers.command(&LaunchOptions::default())?)
+            );
+            // the script carries the access token, only its owner can read it
+            let mut file = File::create(&output)?;
+            #[cfg(unix)]
+            file.set_permissions(std::os::unix::fs::PermissionsExt::from_mode(0o700))?;
+            file.write_all(script.as_bytes())?;
+            println!("{}", output);
         }
-        Commands::Run { version, silent } => {
-            let vers = mvers::get(version).expect("Version not found in MVERS");
-            let std: fn(String) = if silent {
-                |_| {}
-            } else {
-                |e| {
-                    println!("{}", e);
+        #[cfg(target_os = "linux")]
+        Commands::Desktop { command } => match command {
+            DesktopCommands::Install { version } => {
+                let vers = mvers::get(version)?;
+                println!("{}", mdesk::install(&vers)?.display());
+            }
+            DesktopCommands::Rm { version } => {
+                if !mdesk::remove(&version)? {
+                    return Err(McwrError::NotFound(format!("Desktop entry of `{}`", version)));
                 }
+            }
+        },
+        Commands::Config { version, command } => {
+            let scope = match version {
+                Some(version) => Scope::Version(version),
+                None => Scope::Global,
             };
-            vers.run(std, std)?;
-        }
-        Commands::Ls { short } => {
-            let versions = mvers::list()?;
-            for (k, v) in versions.iter() {
-                let message = if short {
-                    format!("{}", k)
-                } else {
-                    format!(
-                        "{} - {} - JAVA: {} - ASSETS: {} - MAIN: {}",
-                        k, "VANILLA", v.java, v.assets, v.main
-                    )
+            // the configuration may be broken here, the home is checked without loading the version
+            if let Scope::Version(version) = &scope
+                && !scope.path().with_file_name(".info").exists()
+            {
+                return Err(McwrError::NotInstalled(version.clone()));
+            }
+            match command {
+            ConfigCommands::Get { key, show_origin } => {
+                let Some(entry) = mconf::entry(&scope, &key)? else {
+                    eprintln!("{} is not set", key);
+                    exit(1);
                 };
-                println!("{}", message);
+                if show_origin {
+                    println!("{}\t{}", entry.origin, entry.raw());
+                } else {
+                    println!("{}", entry.raw());
+                }
+            }
+            ConfigCommands::Set { key, value } => mconf::set(&scope, &key, &value)?,
+            ConfigCommands::Unset { key } => {
+                if !mconf::unset(&scope, &key)? {
+                    eprintln!("{} is not set in {}", key, scope.path().display());
+                    exit(1);
+                }
+            }
+            ConfigCommands::List => {
+                let entries = mconf::entries(&scope)?;
+                let width = entries.iter().map(|entry| entry.key.len()).max().unwrap_or_default();
+                for entry in entries {
+                    println!("{:<width$} = {}  ({})", entry.key, entry.toml(), entry.origin, width = width);
+                }
+            }
+            ConfigCommands::Edit => {
+                let editor = env::var("VISUAL")
+                    .or_else(|_| env::var("EDITOR"))
+                    .unwrap_or_else(|_| String::from("vi"));
+                let mut editor = editor.split_whitespace();
+                let program = editor.next().unwrap_or("vi");
+                let status = process::Command::new(program)
+                    .args(editor)
+                    .arg(scope.path())
+                    .status()?;
+                if !status.success() {
+                    exit(status.code().unwrap_or(1));
+                }
+                mconf::check_file(&scope)?;
+            }
+            ConfigCommands::Path => println!("{}", scope.path().display()),
             }
         }
-        Commands::Remove { version, confirm } => {
-            if !confirm {
-                if !confirmation(format!("¿Quieres eliminar la version {}?", version).as_str()) {
+        Commands::Java { command } => match command {
+            JavaCommands::Ls { system: true } => {
+                for install in mjava::discover() {
+                    let major = install.major.map(|major| major.to_string()).unwrap_or_else(|| String::from("?"));
+                    println!("{:>5}  {}", major, install);
+                }
+            }
+            JavaCommands::Ls { system: false } => {
+                let versions = mvers::list()?;
+                println!("{:>5}  {:<24} {:>10}  VERSIONS", "JAVA", "RUNTIME", "SIZE");
+                for runtime in mjava::runtimes()? {
+                    let major = runtime.major.map(|major| major.to_string()).unwrap_or_else(|| String::from("?"));
+                    let used = runtime.used_by(versions.values());
+                    let used = if used.is_empty() { String::from("-") } else { used.join(", ") };
+                    println!(
+                        "{:>5}  {:<24} {:>6} MiB  {}",
+                        major,
+                        runtime.id,
+                        runtime.size / 1024 / 1024,
+                        used
+                    );
+                }
+            }
+            JavaCommands::Install { major } => {
+                let Some(file) = mjava::fetch(major)? else {
+                    println!("Java {} is already installed", major);
+                    return Ok(());
+                };
+                mvers::start(Downloader::new().with_files(vec![file]))?;
+                println!("{}", mjava::install(major)?.display());
+            }
+            JavaCommands::Rm { runtime, confirm } => {
+                let Some(runtime) = mjava::runtime(&runtime)? else {
+                    return Err(McwrError::NotFound(format!("Runtime `{}`", runtime)));
+                };
+                let used = runtime.used_by(mvers::list()?.values());
+                if !used.is_empty() {
+                    eprintln!("{} is used by {}", runtime.id, used.join(", "));
+                }
+                if !confirm && !confirmation(format!("¿Quieres eliminar el runtime {}?", runtime.id).as_str())? {
                     exit(0);
                 }
+                mjava::remove(&runtime)?;
+            }
+            JavaCommands::Verify { runtime } => {
+                let runtimes = match runtime {
+                    Some(target) => match mjava::runtime(&target)? {
+                        Some(runtime) => vec![runtime],
+                        None => return Err(McwrError::NotFound(format!("Runtime `{}`", target))),
+                    },
+                    None => mjava::runtimes()?,
+                };
+                let mut damaged = false;
+                for runtime in runtimes {
+                    let damages = match mjava::verify(&runtime) {
+                        Ok(damages) => damages,
+                        Err(e) => {
+                            damaged = true;
+                            println!("{}: {}", runtime.id, e);
+                            continue;
+                        }
+                    };
+                    if damages.is_empty() {
+                        println!("{}: ok", runtime.id);
+                        continue;
+                    }
+                    damaged = true;
+                    println!("{}: {} damaged files", runtime.id, damages.len());
+                    for damage in damages {
+                        println!("    {}", damage);
+                    }
+                }
+                if damaged {
+                    exit(1);
+                }
+            }
+        },
+        Commands::Ps => {
+            let running = mproc::list()?;
+            println!("{:>8}  {:<20} {:<16} {:>12} {:>10}", "PID", "VERSION", "ACCOUNT", "UPTIME", "MEMORY");
+            for state in running {
+                let uptime = humantime::format_duration(state.uptime()).to_string();
+                let memory = state
+                    .memory()
+                    .map(|memory| format!("{} MiB", memory / 1024 / 1024))
+                    .unwrap_or_else(|| String::from("-"));
+                println!(
+                    "{:>8}  {:<20} {:<16} {:>12} {:>10}",
+                    state.pid, state.version, state.account, uptime, memory
+                );
+            }
+        }
+        Commands::Kill { target, timeout } => {
+            let targets: Vec<_> = mproc::list()?
+                .into_iter()
+                .filter(|state| state.version == target || state.pid.to_string() == target)
+                .collect();
+            if targets.is_empty() {
+                return Err(McwrError::NotFound(format!("Running game of `{}`", target)));
+            }
+            for state in targets {
+                let killed = mproc::stop(&state, Duration::from_secs(timeout))?;
+                mproc::unregister(&state.version, state.pid);
+                // a game in the foreground records its session itself when it ends
+                if state.log.is_some() {
+                    mvers::record_session(&state.version, UNIX_EPOCH + Duration::from_secs(state.started), state.uptime());
+                }
+                let how = if killed { "killed" } else { "stopped" };
+                println!("{} ({}) {}", state.version, state.pid, how);
             }
-            mvers::remove(version);
         }
-        Commands::Find { version } => {
-            if version.eq("release") {
-                let release = mvers::manifest_latest_release();
-                println!("{}", release);
-                return Ok(());
-            } else if version.eq("snapshot") {
-                let snapshot = mvers::manifest_latest_snapshot();
-                println!("{}", snapshot);
+        Commands::Ls { short, sort, filter } => {
+            // records the sessions of the detached games that ended since the last check
+            mproc::list()?;
+            let mut versions: Vec<LsEntry> = mvers::list()?
+                .into_values()
+                .filter(|version| filter.iter().all(|filter| filter.matches(version)))
+                .map(|version| LsEntry {
+                    loader: version.loader(),
+                    size: mdirs::size_of(&version.home()).unwrap_or_default(),
+                    version,
+                })
+                .collect();
+            versions.sort_by(|a, b| a.version.version.cmp(&b.version.version));
+            match sort {
+                LsSort::Name => {}
+                LsSort::Size => versions.sort_by_key(|entry| std::cmp::Reverse(entry.size)),
+                LsSort::Played => versions.sort_by_key(|entry| std::cmp::Reverse(entry.version.last_played)),
+                LsSort::Playtime => versions.sort_by_key(|entry| std::cmp::Reverse(entry.version.playtime)),
+                LsSort::Java => versions.sort_by_key(|entry| entry.version.java_version),
+            }
+            if output == Output::Json {
+                return print_json(&versions);
+            }
+            if short {
+                versions.iter().for_each(|entry| println!("{}", entry.version.version));
                 return Ok(());
             }
-            let versions = mvers::list_manifest();
-            versions
-                .iter()
-                .filter(|v| v.contains(&version))
-                .for_each(|v| println!("{}", v));
+            let width = versions.iter().map(|entry| entry.version.version.len()).max().unwrap_or_default().max(7);
+            println!(
+                "{:<width$}  {:<8} {:>4} {:>10}  {:<20}  PLAYTIME",
+                "VERSION", "LOADER", "JAVA", "SIZE", "LAST PLAYED",
+                width = width
+            );
+            for entry in versions {
+                let played = entry
+                    .version
+                    .last_played
+                    .map(|played| humantime::format_rfc3339_seconds(UNIX_EPOCH + Duration::from_secs(played)).to_string())
+                    .unwrap_or_else(|| String::from("never"));
+                println!(
+                    "{:<width$}  {:<8} {:>4} {:>6} MiB  {:<20}  {}h {:02}m",
+                    entry.version.version,
+                    entry.loader,
+                    entry.version.java_version,
+                    entry.size / 1024 / 1024,
+                    played,
+                    entry.version.playtime / 3600,
+                    entry.version.playtime / 60 % 60,
+                    width = width
+                );
+            }
+        }
+        Commands::Remove { version, confirm } => {
+            if !confirm && !confirmation(format!("¿Quieres eliminar la version {}?", version).as_str())? {
+                exit(0);
+            }
+            mvers::remove(version)?;
+        }
+        Commands::Clone { version, to } => {
+            let vers = mvers::clone(&version, &to)?;
+            println!("{}", vers.home().display());
+        }
+        Commands::Rename { version, to } => {
+            let vers = mvers::rename(&version, &to)?;
+            // the desktop entry launches the version by its name
+            #[cfg(target_os = "linux")]
+            if mdesk::remove(&version)? {
+                println!("{}", mdesk::install(&vers)?.display());
+            }
+            trace!("VERSION {} RENAMED TO {}", version, vers.version);
+        }
+        Commands::Find { version } => {
+            let latest = match version.as_str() {
+                "release" => Some(mvers::manifest_latest_release()?),
+                "snapshot" => Some(mvers::manifest_latest_snapshot()?),
+                _ => None,
+            };
+            let versions: Vec<ManifestEntry> = mvers::manifest_entries()?
+                .into_iter()
+                .filter(|v| match &latest {
+                    Some(latest) => v.id == *latest,
+                    None => v.id.contains(&version),
+                })
+                .collect();
+            match output {
+                Output::Text => versions.iter().for_each(|v| println!("{}", v.id)),
+                Output::Json => print_json(&versions)?,
+            }
+        }
+        Commands::Info { version } => {
+            let vers = mvers::get(version)?;
+            if output == Output::Json {
+                return print_json(&vers);
+            }
+            let java = mdirs::get().data.join(&vers.java);
+            println!("{:<10} {}", "version", vers.version);
+            println!("{:<10} {}", "type", vers.version_type);
+            println!("{:<10} {}", "main", vers.main);
+            println!("{:<10} {} (Java {})", "java", java.display(), vers.java_version);
+            println!("{:<10} {}", "assets", vers.assets);
+            println!("{:<10} {}", "game dir", vers.game_dir().display());
+            println!("{:<10} {}", "jvm args", vers.jvm_args.join(" "));
+            println!("{:<10} {}", "game args", vers.game_args.join(" "));
+        }
+        Commands::Verify { version } => {
+            let versions = match version {
+                Some(version) => vec![mvers::get(version)?],
+                None => {
+                    let mut versions: Vec<_> = mvers::list()?.into_values().collect();
+                    versions.sort_by(|a, b| a.version.cmp(&b.version));
+                    versions
+                }
+            };
+            let reports: Vec<VerifyReport> = versions
+                .into_iter()
+                .map(|vers| match vers.verify() {
+                    Ok(damaged) => VerifyReport { ok: damaged.is_empty(), version: vers.version, damaged, error: None },
+                    Err(e) => VerifyReport { ok: false, version: vers.version, damaged: Vec::new(), error: Some(e.to_string()) },
+                })
+                .collect();
+            match output {
+                Output::Text => {
+                    for report in &reports {
+                        if let Some(error) = &report.error {
+                            println!("{}: {}", report.version, error);
+                        } else if report.ok {
+                            println!("{}: ok", report.version);
+                        } else {
+                            println!("{}: {} damaged files", report.version, report.damaged.len());
+                        }
+                        for damage in &report.damaged {
+                            println!("    {}", damage);
+                        }
+                    }
+                }
+                Output::Json => print_json(&reports)?,
+            }
+            if reports.iter().any(|report| !report.ok) {
+                exit(1);
+            }
+        }
+        Commands::Crashes { version } => {
+            let vers = mvers::get(version)?;
+            for crash in mcrash::list(&vers) {
+                println!(
+                    "{}  {}",
+                    humantime::format_rfc3339_seconds(crash.time),
+                    crash.path.display()
+                );
+                if let Some(exception) = crash.exception {
+                    println!("    {}", exception);
+                }
+            }
+        }
+        Commands::Logs { version, follow, session } => {
+            let sessions = mlogs::sessions(&version)?;
+            let Some(path) = session.checked_sub(1).and_then(|i| sessions.get(i)) else {
+                let logged = sessions.len();
+                return Err(McwrError::NotFound(format!("Session {} of `{}` ({} logged)", session, version, logged)));
+            };
+            mlogs::print(path, follow)?;
         }
         #[cfg(feature = "export")]
         Commands::Export { version } => {
-            let path =
-                mconf::get_or("export_path", String::from("exports"));
-            if !Path::new(&path).exists() {
-                fs::create_dir(&path).expect("Cannot create export path");
-            }
-            let file = format!("{}/{}.tar.gz", path, version);
-            let versions_path = format!("{}/{}", mconf::get::<&str>("versions"), version);
-            let file = File::create(file)?;
-            let enc = GzEncoder::new(file, Compression::best());
-            let mut tar = Builder::new(enc);
-            tar.append_dir_all(&version, &versions_path)?;
-            tar.finish()?
+            let vers = mvers::get(version)?;
+            let path = Path::new(&mconf::config().export_path).join(format!("{}.tar.gz", vers.version));
+            export(&vers.version, &path).map_err(|source| McwrError::Export {
+                version: vers.version.clone(),
+                path,
+                source,
+            })?;
         }
     }
 
     Ok(())
 }
 
-fn confirmation(message: &str) -> bool {
+/// An installed version listed by `ls`
+#[derive(Debug, Serialize)]
+struct LsEntry {
+    #[serde(flatten)]
+    version: Version,
+    loader: &'static str,
+    /// Size on disk of the home of the version in bytes
+    size: u64,
+}
+
+/// Result of `verify` for a version
+#[derive(Debug, Serialize)]
+struct VerifyReport {
+    version: String,
+    ok: bool,
+    damaged: Vec<Damage>,
+    /// Why the version could not be checked
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
+/// Prints `value` as a JSON document
+fn print_json<T: Serialize>(value: &T) -> Result<(), McwrError> {
+    let json = serde_json::to_string_pretty(value).map_err(io::Error::from)?;
+    writeln!(io::stdout(), "{}", json)?;
+    Ok(())
+}
+/// Prints `event` as a line of JSON
+fn print_event(event: &DownloadEvent) {
+    match serde_json::to_string(event) {
+        Ok(event) => println!("{}", event),
+        Err(e) => warn!("CANNOT SERIALIZE EVENT {:?}: {}", event, e),
+    }
+}
+
+/// Command line of `command`, quoted for a POSIX shell
+fn shell_command(command: &process::Command) -> String {
+    std::iter::once(command.get_program())
+        .chain(command.get_args())
+        .map(|arg| shell_quote(&arg.to_string_lossy()))
+        .collect::<Vec<String>>()
+        .join(" ")
+}
+fn shell_quote(arg: &str) -> String {
+    let safe = !arg.is_empty()
+        && arg
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || "_-+=@%:,./".contains(c));
+    if safe {
+        arg.to_owned()
+    } else {
+        format!("'{}'", arg.replace('\'', "'\\''"))
+    }
+}
+
+/// Versions to download, the ones of `versions` and the lines of `from_file`, without repeating
+fn download_list(mut versions: Vec<String>, from_file: Option<&Path>) -> io::Result<Vec<String>> {
+    if let Some(path) = from_file {
+        let content = fs::read_to_string(path)
+            .map_err(|e| io::Error::new(e.kind(), format!("Cannot read {}: {}", path.display(), e)))?;
+        versions.extend(
+            content
+                .lines()
+                .map(str::trim)
+                .filter(|line| !line.is_empty() && !line.starts_with('#'))
+                .map(str::to_owned),
+        );
+    }
+    let mut seen = HashSet::new();
+    versions.retain(|version| seen.insert(version.clone()));
+    Ok(versions)
+}
+/// Launch options of `vers` given by `options`, saved as its defaults with `--save`
+fn launch_options(vers: &mut Version, options: &RunArgs) -> Result<LaunchOptions, McwrError> {
+    let launch = options.options();
+    if options.reset_defaults {
+        vers.launch = LaunchOptions::default();
+    }
+    if options.save {
+        vers.launch.update(&launch);
+    }
+    if options.save || options.reset_defaults {
+        vers.save()?;
+    }
+    Ok(launch)
+}
+/// Runs `vers` in the foreground, returns the exit code of the game
+fn launch(vers: &Version, options: &LaunchOptions, silent: bool) -> Result<i32, McwrError> {
+    let crashes = CrashWatch::new(vers);
+    let mut game = vers.launch(options)?;
+    let pid = game.pid();
+    trace!("GAME RUNNING WITH PID {}", pid);
+    if !silent {
+        let killer = game.killer();
+        let mut killed = false;
+        // like a program writing to a closed pipe, the game ends when its output cannot be printed
+        game.on_output(Box::new(move |line: OutputLine| {
+            if writeln!(io::stdout(), "{}", mlog4j::render(&line)).is_err() && !killed {
+                killed = true;
+                if let Err(e) = killer.kill() {
+                    warn!("CANNOT KILL THE GAME: {}", e);
+                }
+            }
+        }));
+    }
+    let code = game.wait()?;
+    report_crashes(code, &crashes.check(pid));
+    Ok(code)
+}
+/// Prints the summary of the crashes of a finished game
+fn report_crashes(code: i32, crashes: &[CrashReport]) {
+    if code == 0 && crashes.is_empty() {
+        return;
+    }
+    eprintln!("Game exited with code {}", code);
+    if crashes.is_empty() {
+        eprintln!("No crash report was written");
+    }
+    for crash in crashes {
+        eprintln!("{}", crash);
+    }
+}
+
+/// Writes the home of `version` into the gzipped tarball `path`
+#[cfg(feature = "export")]
+fn export(version: &str, path: &Path) -> io::Result<()> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let versions_path = format!("{}/{}", mconf::config().versions, version);
+    let file = File::create(path)?;
+    let enc = GzEncoder::new(file, Compression::best());
+    let mut tar = Builder::new(enc);
+    tar.append_dir_all(version, &versions_path)?;
+    tar.into_inner()?.finish()?;
+    Ok(())
+}
+
+fn confirmation(message: &str) -> io::Result<bool> {
     print!("{} (s/n): ", message);
-    io::stdout().flush().unwrap(); // Asegura que el mensaje se imprima antes de leer
+    io::stdout().flush()?; // Asegura que el mensaje se imprima antes de leer
     let mut input = String::new();
-    io::stdin().read_line(&mut input).unwrap();
+    io::stdin().read_line(&mut input)?;
     // Convertimos la entrada a minúsculas y removemos espacios y saltos de línea
     let input = input.trim().to_lowercase();
-    if input == "s" || input == "si" {
-        true
-    } else {
-        false
+    Ok(input == "s" || input == "si")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn args_are_consistent() {
+        Args::command().debug_assert();
+    }
+
+    #[test]
+    fn the_last_toggle_wins() {
+        let fullscreen = |args: &[&str]| {
+            let Commands::Run { options, .. } = Args::try_parse_from([&["mcwr", "run", "1.20.4"], args].concat()).unwrap().command
+            else {
+                unreachable!()
+            };
+            options.options().features.fullscreen
+        };
+        assert_eq!(fullscreen(&[]), None);
+        assert_eq!(fullscreen(&["--no-fullscreen"]), Some(false));
+        assert_eq!(fullscreen(&["--fullscreen", "--no-fullscreen"]), Some(false));
+        assert_eq!(fullscreen(&["--no-fullscreen", "--fullscreen"]), Some(true));
+    }
+
+    #[test]
+    fn run_options_require_run() {
+        let error = Args::try_parse_from(["mcwr", "download", "1.20.4", "-m", "2G"]).unwrap_err();
+        assert_eq!(error.kind(), ErrorKind::MissingRequiredArgument);
+        assert!(Args::try_parse_from(["mcwr", "download", "1.20.4", "-R", "-m", "2G"]).is_ok());
     }
 }
@@ -1,26 +1,184 @@
-use mcd::errors::FetchError;
+use std::path::PathBuf;
+
+use mcd::api::ApiClientError;
+use mcd::errors::{CommandError, FetchError};
 use thiserror::Error;
 
+/// Exit codes of the error categories, 2 is kept for the usage errors of clap
+pub const EXIT_FAILURE: i32 = 1;
+pub const EXIT_CONFIG: i32 = 3;
+pub const EXIT_MANIFEST: i32 = 4;
+pub const EXIT_DOWNLOAD: i32 = 5;
+pub const EXIT_METADATA: i32 = 6;
+pub const EXIT_LAUNCH: i32 = 7;
+pub const EXIT_EXPORT: i32 = 8;
+
+/// Every error mcwr can end with, grouped in categories with their own exit code
+#[derive(Debug, Error)]
+pub enum McwrError {
+    #[error(transparent)]
+    Config(#[from] ConfigError),
+    #[error("Cannot read the version manifest {path}, check your connection or remove it to download it again")]
+    Manifest {
+        path: String,
+        #[source]
+        source: ApiClientError,
+    },
+    #[error("Version `{0}` does not exist, search it with `mcwr find`")]
+    UnknownVersion(String),
+    #[error("Cannot get the client JSON of {version}")]
+    Client {
+        version: String,
+        #[source]
+        source: ApiClientError,
+    },
+    #[error("Cannot find `{parent}`, the parent of `{version}`, in the manifest or in custom-versions/")]
+    MissingParent { version: String, parent: String },
+    #[error("The versions that `{0}` inherits from form a cycle")]
+    InheritanceCycle(String),
+    #[error("Cannot download the version")]
+    Download(#[from] DownloadError),
+    #[error("Version `{0}` is not installed, download it with `mcwr download {0}`")]
+    NotInstalled(String),
+    #[error("Version `{0}` already exists, remove it first with `mcwr remove {0}`")]
+    AlreadyInstalled(String),
+    #[error("`{0}` is not a valid version name")]
+    InvalidName(String),
+    #[error("Version `{0}` is running, stop it first with `mcwr kill {0}`")]
+    Running(String),
+    #[error("Version `{0}` has no client JSON, download it again to verify it")]
+    NoClient(String),
+    #[error("Cannot read the installed versions")]
+    Metadata(#[from] ReadingError),
+    #[error("Cannot save the metadata of the version")]
+    Save(#[from] WritingError),
+    #[error("Cannot launch the game, check its Java with `mcwr java verify`")]
+    Launch(#[from] CommandError),
+    #[error("Version `{version}` has no quick play, it cannot open a {target} on start")]
+    QuickPlayUnsupported { version: String, target: &'static str },
+    #[error("Cannot find {0}, list the Java installs with `mcwr java ls --system`")]
+    NoJava(String),
+    #[error("{0} not found")]
+    NotFound(String),
+    #[error("Cannot export {version} to {}", path.display())]
+    Export {
+        version: String,
+        path: PathBuf,
+        #[source]
+        source: std::io::Error,
+    },
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+}
+impl McwrError {
+    /// Exit code of the category of the error
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            McwrError::Config(_) => EXIT_CONFIG,
+            McwrError::Manifest { .. }
+            | McwrError::UnknownVersion(_)
+            | McwrError::Client { .. }
+            | McwrError::MissingParent { .. }
+            | McwrError::InheritanceCycle(_) => EXIT_MANIFEST,
+            McwrError::Download(_) => EXIT_DOWNLOAD,
+            McwrError::NotInstalled(_)
+            | McwrError::AlreadyInstalled(_)
+            | McwrError::InvalidName(_)
+            | McwrError::NoClient(_)
+            | McwrError::Metadata(_)
+            | McwrError::Save(_) => EXIT_METADATA,
+            McwrError::Running(_) | McwrError::NotFound(_) => EXIT_FAILURE,
+            McwrError::Launch(_) | McwrError::QuickPlayUnsupported { .. } | McwrError::NoJava(_) => EXIT_LAUNCH,
+            McwrError::Export { .. } => EXIT_EXPORT,
+            McwrError::Io(_) => EXIT_FAILURE,
+        }
+    }
+}
+/// `e` and its causes in a line
+pub fn chain(e: &dyn std::error::Error) -> String {
+    let mut line = e.to_string();
+    let mut source = e.source();
+    while let Some(cause) = source {
+        line.push_str(&format!(": {}", cause));
+        source = cause.source();
+    }
+    line
+}
+impl From<FetchError> for McwrError {
+    fn from(e: FetchError) -> Self {
+        McwrError::Download(e.into())
+    }
+}
+#[cfg(feature = "interactive")]
+impl From<dialoguer::Error> for McwrError {
+    fn from(e: dialoguer::Error) -> Self {
+        match e {
+            dialoguer::Error::IO(e) => McwrError::Io(e),
+        }
+    }
+}
+
 #[derive(Debug, Error)]
 pub enum DownloadError {
-    #[error("FETCH ERROR")]
+    #[error("Cannot resolve the files of the version")]
     FetchError(#[from] FetchError),
-    #[error("META ERROR")]
-    InitMetaError(#[from] WritingError)
+    #[error("Cannot download the files, check your connection")]
+    Files(#[source] std::io::Error),
+    #[error("{0} files were not downloaded or are damaged, download the version again")]
+    Incomplete(usize),
+    #[error("Cannot write the metadata of the version")]
+    InitMetaError(#[from] WritingError),
+    #[error("Cannot install the downloaded files")]
+    Io(#[from] std::io::Error),
 }
 
 #[derive(Debug, Error)]
 pub enum ReadingError {
-    #[error("Error reading")]
-    ReadError(#[from] std::io::Error),
-    #[error("Error deserializing file")]
-    DeserializeError(#[from] toml::de::Error)
+    #[error("Cannot read {}", .0.display())]
+    ReadError(PathBuf, #[source] std::io::Error),
+    #[error("Broken metadata in {}, download the version again", .0.display())]
+    DeserializeError(PathBuf, #[source] toml::de::Error),
 }
 
 #[derive(Debug, Error)]
 pub enum WritingError {
-    #[error("Error writing")]
-    ReadError(#[from] std::io::Error),
-    #[error("Error serializing file")]
-    DeserializeError(#[from] toml::ser::Error)
+    #[error("Cannot write {0}")]
+    ReadError(String, #[source] std::io::Error),
+    #[error("Cannot serialize the metadata")]
+    DeserializeError(#[from] toml::ser::Error),
+}
+
+#[derive(Debug, Error)]
+pub enum ConfigError {
+    #[error("Error loading the configuration")]
+    Load(#[from] config::ConfigError),
+    #[error("Invalid value of `{key}` in the configuration: {reason}")]
+    Invalid { key: String, reason: String },
+    #[error("Unknown configuration key `{0}`")]
+    UnknownKey(String),
+    #[error("`{0}` cannot be set per version")]
+    NotPerVersion(String),
+    #[error("Error parsing the configuration file")]
+    Parse(#[from] toml_edit::TomlError),
+    #[error("Cannot access the configuration file")]
+    Io(#[from] std::io::Error),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn exit_code_of_the_category() {
+        let io = || std::io::Error::other("test");
+        assert_eq!(McwrError::Config(ConfigError::UnknownKey(String::from("key"))).exit_code(), EXIT_CONFIG);
+        assert_eq!(McwrError::UnknownVersion(String::from("1.20.4")).exit_code(), EXIT_MANIFEST);
+        assert_eq!(McwrError::Download(DownloadError::Files(io())).exit_code(), EXIT_DOWNLOAD);
+        assert_eq!(McwrError::NotInstalled(String::from("1.20.4")).exit_code(), EXIT_METADATA);
+        assert_eq!(McwrError::NoJava(String::from("java")).exit_code(), EXIT_LAUNCH);
+        let export = McwrError::Export { version: String::from("1.20.4"), path: PathBuf::from("x"), source: io() };
+        assert_eq!(export.exit_code(), EXIT_EXPORT);
+        assert_eq!(McwrError::NotFound(String::from("Runtime `17`")).exit_code(), EXIT_FAILURE);
+        assert_eq!(McwrError::Io(io()).exit_code(), EXIT_FAILURE);
+    }
 }
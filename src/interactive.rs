@@ -1,22 +1,25 @@
-use std::{io::Write, thread, time::Duration};
+use std::{io::Write, path::Path, thread, time::Duration};
 
 use console::{style, Term};
 use dialoguer::{theme::ColorfulTheme, Confirm, Select};
-use mcd::api::ApiClientUtil;
 use translateutil::translate;
-use anyhow::Result;
 
-use crate::{mconf, mvers, temp};
+use crate::errors::McwrError;
+use crate::mcrash::CrashWatch;
+use crate::mvers::{LaunchOptions, Version};
+use crate::{mlog4j, mprogress, mvers, temp};
+
+type Result<T> = std::result::Result<T, McwrError>;
 
 
 pub fn run() -> Result<()> {
     let term = Term::stdout();
-    term.clear_screen().unwrap();
+    term.clear_screen()?;
 
     println!(translate!("info.initial"));
     welcome();
     loop {
-        match prompt_user_action() {
+        match prompt_user_action()? {
             Action::ShowDownloadedVersions => {
                 show_downloaded_versions(&term)?;
             }
@@ -44,7 +47,7 @@ fn view_metadata(term: &Term) -> Result<()> {
     print_system_message(translate!("meta.view.initial"));
     let version = select_downloaded_version(term)?;
     print_system_message(translate!("meta.view.message"));
-    let version = mvers::get(version).unwrap();
+    let version = mvers::get(version)?;
     print_meta("java", version.java);
     print_meta("main", version.main);
     print_meta("version", version.version);
@@ -86,7 +89,7 @@ fn show_downloaded_versions(term: &Term) -> Result<()> {
 }
 fn download_version(term: &Term) -> Result<()> {
     print_system_message(translate!("dwld.initial"));
-    let version_id = select_version(&term);
+    let version_id = select_version(term)?;
     print_system_message(
         format!(
             "{} {} {}",
@@ -102,12 +105,12 @@ fn download_version(term: &Term) -> Result<()> {
             translate!("dwld.prompt.assets.yes"),
             translate!("dwld.prompt.assets.no"),
         ],
-    );
+    )?;
     print_system_message(translate!("dwld.cooldown.message"));
-    counter_back(3);
-    let apic = ApiClientUtil::new(&mconf::get::<String>("manifest"))?;
-    let client = apic.fetch(&version_id, &temp!("mcwr-client.tmp"))?;
-    mvers::download(&client, assets == 0)?;
+    counter_back(3)?;
+    let json = temp!("mcwr-client.tmp");
+    let client = mvers::fetch(&version_id, &json)?;
+    mvers::download(&client, Path::new(&json), assets == 0, mprogress::bars())?;
 
     print_system_message(translate!("dwld.done"));
     let launch = open_select(
@@ -116,28 +119,24 @@ fn download_version(term: &Term) -> Result<()> {
             translate!("dwld.prompt.launch.yes"),
             translate!("dwld.prompt.launch.no"),
         ],
-    );
+    )?;
     if launch == 0 {
         print_system_message(translate!("dwld.launch.initial"));
-        let version = mvers::get(version_id).expect("VERSION NOT FOUND");
-        version.run(
-            |l| println!("{}", l),
-            |e| println!("{}", e),
-        );
+        let version = mvers::get(version_id)?;
+        play(&version)?;
         print_system_message(translate!("info.finish"));
         std::process::exit(0);
     }
     Ok(())
 }
-fn select_version(_: &Term) -> String {
-    let versions = mvers::list_manifest();
+fn select_version(_: &Term) -> Result<String> {
+    let versions = mvers::list_manifest()?;
     let selection = dialoguer::FuzzySelect::with_theme(&ColorfulTheme::default())
         .with_prompt(system_message(translate!("select.version.prompt")))
         .default(0)
         .items(versions.as_slice())
-        .interact()
-        .unwrap();
-    versions[selection].clone()
+        .interact()?;
+    Ok(versions[selection].clone())
 }
 fn select_downloaded_version(term: &Term) -> Result<String> {
     let versions = mvers::list()?
@@ -153,7 +152,7 @@ fn select_downloaded_version(term: &Term) -> Result<String> {
         if !confirm {
             std::process::exit(0);
         } else {
-            download_version(term);
+            download_version(term)?;
             return select_downloaded_version(term);
         }
     }
@@ -174,7 +173,7 @@ fn delete_version(term: &Term) -> Result<()> {
         .interact()?;
 
     if confirmation {
-        mvers::remove(version_id);
+        mvers::remove(version_id)?;
     } else {
         print_system_message(translate!("delete.abort"));
     }
@@ -186,15 +185,29 @@ fn run_game(term: &Term) -> Result<()> {
     print_system_message(translate!("run.initial"));
     let version_id = select_downloaded_version(term)?;
     print_system_message(translate!("run.loading"));
-    let version = mvers::get(version_id).expect("VERSION NOT EXIST");
-    version.run(
-        |l| println!("{}", l),
-        |e| println!("{}", e),
-    );
+    let version = mvers::get(version_id)?;
+    play(&version)?;
     print_system_message(translate!("run.finish"));
     print_system_message(translate!("info.finish"));
     std::process::exit(0);
 }
+/// Runs the game printing its output, and the crash reports if it crashes
+fn play(version: &Version) -> Result<()> {
+    let crashes = CrashWatch::new(version);
+    let mut game = version.launch(&LaunchOptions::default())?;
+    let pid = game.pid();
+    game.on_stdout(Box::new(|l| println!("{}", mlog4j::render(&l))));
+    game.on_stderr(Box::new(|e| println!("{}", mlog4j::render(&e))));
+    let code = game.wait()?;
+    let crashes = crashes.check(pid);
+    if code != 0 || !crashes.is_empty() {
+        print_system_message(&format!("{} {}", translate!("run.crashed"), code));
+        for crash in crashes {
+            println!("{}", style(crash).red());
+        }
+    }
+    Ok(())
+}
 
 enum Action {
     ShowDownloadedVersions,
@@ -204,7 +217,7 @@ enum Action {
     ViewMetadata,
     Exit,
 }
-fn prompt_user_action() -> Action {
+fn prompt_user_action() -> Result<Action> {
     let selection = open_select(
         system_message(translate!("options.title")).as_str(),
         vec![
@@ -220,15 +233,15 @@ fn prompt_user_action() -> Action {
                 .to_string()
                 .as_str(),
         ],
-    );
-    match selection {
+    )?;
+    Ok(match selection {
         0 => Action::ShowDownloadedVersions,
         1 => Action::DownloadVersion,
         2 => Action::DeleteVersion,
         3 => Action::RunGame,
         4 => Action::ViewMetadata,
         _ => Action::Exit,
-    }
+    })
 }
 fn welcome() {
     println!(
@@ -238,13 +251,11 @@ fn welcome() {
         style(translate!("welcome.2")).bold(),
     );
 }
-fn open_select(prompt: &str, options: Vec<&str>) -> usize {
-    let selection = Select::with_theme(&ColorfulTheme::default())
+fn open_select(prompt: &str, options: Vec<&str>) -> Result<usize> {
+    Ok(Select::with_theme(&ColorfulTheme::default())
         .with_prompt(prompt)
         .items(&options)
-        .interact()
-        .unwrap();
-    selection
+        .interact()?)
 }
 fn system_message(message: &str) -> String {
     format!("{}", style(message).bold().bright().color256(208))
@@ -252,16 +263,17 @@ fn system_message(message: &str) -> String {
 fn print_system_message(message: &str) {
     println!("{}", system_message(message));
 }
-fn counter_back(seconds: u32) {
+fn counter_back(seconds: u32) -> std::io::Result<()> {
     print!("{}", seconds);
-    std::io::stdout().flush().unwrap();
+    std::io::stdout().flush()?;
     for i in (0..seconds).rev() {
         for _ in (0..4).rev() {
             print!(".");
-            std::io::stdout().flush().unwrap();
+            std::io::stdout().flush()?;
             thread::sleep(Duration::from_millis(250));
         }
         print!("{}", i);
-        std::io::stdout().flush().unwrap();
+        std::io::stdout().flush()?;
     }
+    Ok(())
 }
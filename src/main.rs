@@ -1,58 +1,89 @@
-use std::path::Path;
-use std::{env, fs};
+use std::path::{Path, PathBuf};
+use std::{env, fs, process};
 
-use anyhow::Ok;
-use anyhow::Result;
 use log::trace;
 
+use crate::errors::McwrError;
+
 mod cline;
 #[cfg(feature = "interactive")]
 mod interactive;
 mod mconf;
+mod mcrash;
+mod mdirs;
+#[cfg(target_os = "linux")]
+mod mdesk;
+mod mfeat;
+mod minherit;
+mod mjava;
+mod mlog4j;
+mod mlogs;
+mod mproc;
+mod mprogress;
 mod errors;
 mod mvers;
 #[macro_use]
-
 mod mtmp;
 
-fn main() -> Result<()> {
+fn main() {
     // initialize env_logger
     env_logger::init();
-    init();
+    if let Err(e) = start() {
+        eprintln!("Error: {}", e);
+        let mut source = std::error::Error::source(&e);
+        while let Some(cause) = source {
+            eprintln!("  caused by: {}", cause);
+            source = cause.source();
+        }
+        process::exit(e.exit_code());
+    }
+}
+fn start() -> Result<(), McwrError> {
     #[cfg(feature = "interactive")]
     if cfg!(feature = "interactive") && env::args().len() == 1 {
         trace!("RUNNING INTERACTIVE");
-        interactive::run()?;
-        return Ok(());
+        init(None, true)?;
+        return interactive::run();
     }
     trace!("NORMAL CLIENT RUN");
-    cline::run()?;
-    Ok(())
+    cline::run()
 }
 
-fn init() {
+/// Resolves the directories of mcwr, in `root` for the portable mode, and loads the configuration
+/// unless `load_config` is false, so a broken configuration can still be fixed with `mcwr config`
+pub fn init(root: Option<PathBuf>, load_config: bool) -> Result<(), McwrError> {
     trace!("CALL TO INIT CHECK");
-    let user_conf_path = Path::new("mcwr.conf");
+    let dirs = mdirs::init(root)?;
+    mdirs::migrate()?;
+    let user_conf_path = dirs.config_file();
     let default_config = include_str!("mcwr.default.conf");
     if !user_conf_path.exists() {
         trace!("CONFING FILE NOT EXIST, WRITING DEFAULT");
         // escribimos el valor por defecto
-        fs::write(user_conf_path, default_config).unwrap();
+        fs::create_dir_all(&dirs.config)?;
+        fs::write(&user_conf_path, default_config)?;
     }
 
+    if !load_config {
+        return Ok(());
+    }
+    // cargamos y validamos la configuracion
+    let config = mconf::load()?;
+
     // establecemos los paths
-    let versions_path = mconf::get::<String>("versions");
-    let assets_path = mconf::get::<String>("resources");
+    let versions_path = &config.versions;
+    let assets_path = &config.resources;
     let versions_path = Path::new(&versions_path);
     let assets_path = Path::new(&assets_path);
 
     // si no existen los creamos
     if !versions_path.exists() {
         trace!("VERSIONS DIR NOT EXISTS, WRITING DEFAULT");
-        fs::create_dir(versions_path).unwrap();
+        fs::create_dir_all(versions_path)?;
     }
     if !assets_path.exists() {
         trace!("ASSETS DIR NOT EXISTS, WRITING DEFAULT");
-        fs::create_dir(assets_path).unwrap();
+        fs::create_dir_all(assets_path)?;
     }
+    Ok(())
 }
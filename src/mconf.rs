@@ -1,23 +1,502 @@
+use std::collections::{BTreeMap, HashMap};
+use std::env;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
 use config::Config;
-use serde::Deserialize;
-
-/// devuelve todas la configuraciones
-pub fn config() -> Config {
-    Config::builder()
-        .add_source(config::File::with_name("mcwr.conf").format(config::FileFormat::Toml))
-        .add_source(config::Environment::with_prefix("MCW_CONFIG"))
-        .build()
-        .unwrap()
-}
-/// optiene una configuracion
-pub fn get<'a, T: Deserialize<'a>>(key: &str) -> T {
-    config().get(key).unwrap()
-}
-pub fn get_or<'a, T: Deserialize<'a>>(key: &str, default: T) -> T {
-    let val = config().get(key);
-    if val.is_err() {
-        return default;
-    } else {
-        return val.unwrap();
+use log::{trace, warn};
+use serde::{Deserialize, Serialize};
+
+use crate::errors::ConfigError;
+use crate::mdirs::{self, Dirs};
+
+static ENV_PREFIX: &str = "MCW_CONFIG";
+/// Overrides of the configuration inside the home of a version
+static VERSION_FILE: &str = "mcwr.toml";
+static CONFIG: OnceLock<McwrConfig> = OnceLock::new();
+
+/// Configuracion de mcwr, `mcwr.conf` sobreescrita por las variables `MCW_CONFIG_*`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct McwrConfig {
+    /// Directory of the installed versions
+    pub versions: String,
+    /// URL or path of the version manifest
+    pub manifest: String,
+    pub tmp: String,
+    /// Directory of the downloaded Java runtimes
+    pub java: String,
+    /// Game directory
+    pub pwd: String,
+    pub max_current_downloads: usize,
+    /// Directory of the assets
+    pub resources: String,
+    pub logs_max_sessions: usize,
+    pub logs_max_size: u64,
+    pub export_path: String,
+    /// JVM flags preset used when a launch names none
+    pub preset: Option<String>,
+    pub presets: HashMap<String, Vec<String>>,
+    pub uuid: String,
+    pub token: String,
+    pub clientid: String,
+    pub xuid: String,
+    pub usertype: String,
+    /// Feature flags of the rule-gated arguments of the client JSON
+    pub options: HashMap<String, bool>,
+    /// Values of the placeholders of the launch arguments
+    pub data: HashMap<String, String>,
+}
+impl Default for McwrConfig {
+    fn default() -> Self {
+        McwrConfig {
+            versions: String::from("versions"),
+            manifest: String::from("manifest.json"),
+            tmp: String::from(".tmp"),
+            java: String::from("java"),
+            pwd: String::from("workdir"),
+            max_current_downloads: 5,
+            resources: String::from("assets"),
+            logs_max_sessions: 10,
+            logs_max_size: 50 * 1024 * 1024,
+            export_path: String::from("exports"),
+            preset: None,
+            presets: HashMap::new(),
+            uuid: String::from("000"),
+            token: String::from("000"),
+            clientid: String::from("000"),
+            xuid: String::from("000"),
+            usertype: String::from("msa"),
+            options: HashMap::new(),
+            data: HashMap::new(),
+        }
+    }
+}
+impl McwrConfig {
+    /// Makes the relative paths absolute, data paths under the data directory and
+    /// cache paths under the cache directory
+    fn resolve(&mut self, dirs: &Dirs) {
+        let resolve = |path: &mut String, base: &Path| *path = base.join(&*path).to_string_lossy().into_owned();
+        for path in [&mut self.versions, &mut self.java, &mut self.pwd, &mut self.resources, &mut self.export_path] {
+            resolve(path, &dirs.data);
+        }
+        for path in [&mut self.manifest, &mut self.tmp] {
+            resolve(path, &dirs.cache);
+        }
+    }
+    /// Checks the values that deserialize fine but cannot work
+    fn validate(&self) -> Result<(), ConfigError> {
+        let paths = [
+            ("versions", &self.versions),
+            ("manifest", &self.manifest),
+            ("java", &self.java),
+            ("pwd", &self.pwd),
+            ("resources", &self.resources),
+        ];
+        for (key, value) in paths {
+            if value.trim().is_empty() {
+                return Err(invalid(key, "cannot be empty"));
+            }
+        }
+        if self.max_current_downloads == 0 {
+            return Err(invalid("max_current_downloads", "must be at least 1"));
+        }
+        if self.logs_max_sessions == 0 {
+            return Err(invalid("logs_max_sessions", "must be at least 1"));
+        }
+        let preset = self.preset.as_ref().filter(|preset| !preset.is_empty());
+        if let Some(preset) = preset.filter(|preset| !self.presets.contains_key(*preset)) {
+            return Err(invalid("preset", &format!("there is no preset `{}` in [presets]", preset)));
+        }
+        Ok(())
+    }
+}
+
+/// Overrides of the configuration for a single version, read from `mcwr.toml` in its home.
+/// They are layered over the global configuration and under the saved launch options
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct VersionConfig {
+    /// Game directory of the version
+    pub pwd: Option<String>,
+    pub preset: Option<String>,
+    /// Java home or binary, or `auto`
+    pub java: Option<String>,
+    pub memory: Option<String>,
+    pub jvm_args: Vec<String>,
+    pub options: HashMap<String, bool>,
+    pub data: HashMap<String, String>,
+}
+impl VersionConfig {
+    fn validate(&self) -> Result<(), ConfigError> {
+        let preset = self.preset.as_ref().filter(|preset| !preset.is_empty());
+        // the presets are checked when the global configuration is valid
+        if let (Some(preset), Ok(config)) = (preset, load())
+            && !config.presets.contains_key(preset)
+        {
+            return Err(invalid("preset", &format!("there is no preset `{}` in [presets]", preset)));
+        }
+        Ok(())
+    }
+}
+/// Overrides of `version`, empty when it has no `mcwr.toml`
+pub fn version_config(version: &str) -> Result<VersionConfig, ConfigError> {
+    let doc = read_file(&Scope::Version(version.to_owned()))?;
+    let overrides = parse_version(&doc)?;
+    overrides.validate()?;
+    Ok(overrides)
+}
+
+/// Carga y valida la configuracion, solo se lee una vez
+pub fn load() -> Result<&'static McwrConfig, ConfigError> {
+    if let Some(config) = CONFIG.get() {
+        return Ok(config);
+    }
+    let dirs = mdirs::get();
+    let file = dirs.config_file();
+    trace!("LOADING CONFIG {:?}", file);
+    let source = Config::builder()
+        .add_source(config::File::from(file).format(config::FileFormat::Toml))
+        .add_source(config::Environment::with_prefix(ENV_PREFIX).try_parsing(true))
+        .build()?;
+    warn_unknown(&source);
+    let mut config: McwrConfig = source.try_deserialize()?;
+    config.validate()?;
+    config.resolve(dirs);
+    trace!("CONFIG LOADED {:?}", config);
+    Ok(CONFIG.get_or_init(|| config))
+}
+/// devuelve la configuracion cargada al iniciar, `init` la carga antes de cualquier comando
+/// salvo `mcwr config`, que no la usa
+pub fn config() -> &'static McwrConfig {
+    CONFIG.get().expect("CONFIG USED BEFORE LOAD")
+}
+
+/// Where the effective value of a key comes from
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Origin {
+    File,
+    /// Environment variable overriding the file
+    Env(String),
+    Default,
+    /// `mcwr.toml` of the version
+    Version,
+}
+
+/// Configuration file read and written by `mcwr config`
+#[derive(Debug, Clone)]
+pub enum Scope {
+    Global,
+    /// Overrides of a version
+    Version(String),
+}
+impl Scope {
+    pub fn path(&self) -> PathBuf {
+        match self {
+            Scope::Global => mdirs::get().config_file(),
+            Scope::Version(version) => Path::new(&load_versions_dir()).join(version).join(VERSION_FILE),
+        }
+    }
+    fn kind(&self, key: &str) -> Result<Kind, ConfigError> {
+        let kind = match self {
+            Scope::Global => global_kind(key),
+            Scope::Version(_) => version_kind(key),
+        };
+        kind.ok_or_else(|| match (self, global_kind(key)) {
+            (Scope::Version(_), Some(_)) => ConfigError::NotPerVersion(key.to_owned()),
+            _ => ConfigError::UnknownKey(key.to_owned()),
+        })
+    }
+    /// Validates a configuration file before it is written
+    fn check(&self, doc: &toml_edit::DocumentMut) -> Result<(), ConfigError> {
+        match self {
+            Scope::Global => {
+                let config: McwrConfig = toml::from_str(&doc.to_string()).map_err(|e| {
+                    let key = e.span().and_then(|span| key_at(doc, span.start)).unwrap_or_default();
+                    invalid(&key, e.message())
+                })?;
+                config.validate()
+            }
+            Scope::Version(_) => parse_version(doc)?.validate(),
+        }
+    }
+}
+impl fmt::Display for Origin {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Origin::File => write!(f, "file"),
+            Origin::Env(var) => write!(f, "env {}", var),
+            Origin::Default => write!(f, "default"),
+            Origin::Version => write!(f, "version"),
+        }
+    }
+}
+
+/// Effective value of a key of the configuration, the keys of tables are `table.key`
+#[derive(Debug, Clone)]
+pub struct Entry {
+    pub key: String,
+    pub value: toml::Value,
+    pub origin: Origin,
+}
+impl Entry {
+    /// The value as written in the configuration file
+    pub fn toml(&self) -> String {
+        self.value.to_string()
+    }
+    /// The value without the quotes of the strings
+    pub fn raw(&self) -> String {
+        match &self.value {
+            toml::Value::String(value) => value.clone(),
+            value => value.to_string(),
+        }
+    }
+}
+
+/// Effective values of the configuration, sorted by key, with the overrides of the version
+/// for [`Scope::Version`]. Paths are shown as written, before being resolved against the
+/// data and cache directories
+pub fn entries(scope: &Scope) -> Result<Vec<Entry>, ConfigError> {
+    let mut entries = BTreeMap::new();
+    flatten(defaults(), Origin::Default, &mut entries);
+    let file: toml::Table = read_file(&Scope::Global)?.to_string().parse().unwrap_or_default();
+    flatten(file, Origin::File, &mut entries);
+    // only the keys of the root of the configuration can be set with the environment
+    for key in defaults().keys().map(String::as_str).chain(["preset"]) {
+        let var = env_var(key);
+        if let Ok(value) = env::var(&var) {
+            entries.insert(key.to_owned(), (toml::Value::String(value), Origin::Env(var)));
+        }
+    }
+    if let Scope::Version(_) = scope {
+        let overrides = read_file(scope)?;
+        parse_version(&overrides)?;
+        let overrides: toml::Table = overrides.to_string().parse().unwrap_or_default();
+        flatten(overrides, Origin::Version, &mut entries);
+    }
+    Ok(entries
+        .into_iter()
+        .map(|(key, (value, origin))| Entry { key, value, origin })
+        .collect())
+}
+/// Effective value of `key`
+pub fn entry(scope: &Scope, key: &str) -> Result<Option<Entry>, ConfigError> {
+    if global_kind(key).is_none() && version_kind(key).is_none() {
+        return Err(ConfigError::UnknownKey(key.to_owned()));
+    }
+    Ok(entries(scope)?.into_iter().find(|entry| entry.key == key))
+}
+/// Writes `value` to `key` in the configuration file, keeping its comments and format.
+/// The value is checked against the type of the key and the resulting configuration validated
+pub fn set(scope: &Scope, key: &str, value: &str) -> Result<(), ConfigError> {
+    let mut value = scope.kind(key)?.parse(key, value)?;
+    let mut doc = read_file(scope)?;
+    let (table, key) = match key.split_once('.') {
+        Some((table, key)) => {
+            let table = doc
+                .entry(table)
+                .or_insert_with(|| toml_edit::Item::Table(toml_edit::Table::new()))
+                .as_table_like_mut()
+                .ok_or_else(|| invalid(table, "is not a table"))?;
+            (table, key)
+        }
+        None => (doc.as_table_mut() as &mut dyn toml_edit::TableLike, key),
+    };
+    match table.get_mut(key) {
+        Some(item) => {
+            // the new value keeps the spacing and comments of the old one
+            if let Some(old) = item.as_value() {
+                *value.decor_mut() = old.decor().clone();
+            }
+            *item = toml_edit::value(value);
+        }
+        None => {
+            table.insert(key, toml_edit::value(value));
+        }
+    }
+    write_file(scope, &doc)
+}
+/// Removes `key` from the configuration file, returns whether it was set
+pub fn unset(scope: &Scope, key: &str) -> Result<bool, ConfigError> {
+    scope.kind(key)?;
+    let mut doc = read_file(scope)?;
+    let removed = match key.split_once('.') {
+        Some((table, key)) => doc
+            .get_mut(table)
+            .and_then(|table| table.as_table_like_mut())
+            .and_then(|table| table.remove(key))
+            .is_some(),
+        None => doc.remove(key).is_some(),
+    };
+    if removed {
+        write_file(scope, &doc)?;
+    }
+    Ok(removed)
+}
+/// Validates the configuration file, after it is edited by hand
+pub fn check_file(scope: &Scope) -> Result<(), ConfigError> {
+    scope.check(&read_file(scope)?)
+}
+
+/// Type of the values of a key
+enum Kind {
+    Str,
+    Int,
+    Bool,
+    List,
+}
+impl Kind {
+    fn parse(&self, key: &str, raw: &str) -> Result<toml_edit::Value, ConfigError> {
+        let value = match self {
+            Kind::Str => toml_edit::Value::from(raw),
+            Kind::Int => raw
+                .parse::<i64>()
+                .map_err(|_| invalid(key, "expected an integer"))?
+                .into(),
+            Kind::Bool => raw
+                .parse::<bool>()
+                .map_err(|_| invalid(key, "expected true or false"))?
+                .into(),
+            // a TOML array, or the items separated by spaces
+            Kind::List if raw.trim_start().starts_with('[') => raw
+                .parse::<toml_edit::Value>()
+                .ok()
+                .filter(|value| value.as_array().is_some_and(|array| array.iter().all(|item| item.is_str())))
+                .ok_or_else(|| invalid(key, "expected an array of strings"))?,
+            Kind::List => raw.split_whitespace().collect::<toml_edit::Array>().into(),
+        };
+        Ok(value)
+    }
+}
+fn global_kind(key: &str) -> Option<Kind> {
+    match key.split_once('.') {
+        Some(("data", _)) => Some(Kind::Str),
+        Some(("options", _)) => Some(Kind::Bool),
+        Some(("presets", _)) => Some(Kind::List),
+        Some(_) => None,
+        None if key == "preset" => Some(Kind::Str),
+        None => match defaults().get(key)? {
+            toml::Value::String(_) => Some(Kind::Str),
+            toml::Value::Integer(_) => Some(Kind::Int),
+            _ => None,
+        },
+    }
+}
+fn version_kind(key: &str) -> Option<Kind> {
+    match key.split_once('.') {
+        Some(("data", _)) => Some(Kind::Str),
+        Some(("options", _)) => Some(Kind::Bool),
+        Some(_) => None,
+        None => match key {
+            "pwd" | "preset" | "java" | "memory" => Some(Kind::Str),
+            "jvm_args" => Some(Kind::List),
+            _ => None,
+        },
+    }
+}
+fn parse_version(doc: &toml_edit::DocumentMut) -> Result<VersionConfig, ConfigError> {
+    toml::from_str(&doc.to_string()).map_err(|e| {
+        let key = e.span().and_then(|span| key_at(doc, span.start)).unwrap_or_default();
+        invalid(&key, e.message())
+    })
+}
+/// Directory of the versions, also when the configuration is not loaded by `mcwr config`
+fn load_versions_dir() -> String {
+    match load() {
+        Ok(config) => config.versions.clone(),
+        Err(_) => mdirs::get().data.join(&McwrConfig::default().versions).to_string_lossy().into_owned(),
+    }
+}
+fn defaults() -> toml::Table {
+    toml::Table::try_from(McwrConfig::default()).unwrap_or_default()
+}
+fn flatten(table: toml::Table, origin: Origin, entries: &mut BTreeMap<String, (toml::Value, Origin)>) {
+    for (key, value) in table {
+        match value {
+            toml::Value::Table(table) => {
+                for (sub, value) in table {
+                    entries.insert(format!("{}.{}", key, sub), (value, origin.clone()));
+                }
+            }
+            value => {
+                entries.insert(key, (value, origin.clone()));
+            }
+        }
+    }
+}
+fn env_var(key: &str) -> String {
+    format!("{}_{}", ENV_PREFIX, key.to_uppercase())
+}
+fn read_file(scope: &Scope) -> Result<toml_edit::DocumentMut, ConfigError> {
+    let file = scope.path();
+    let content = match fs::read_to_string(&file) {
+        Ok(content) => content,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => String::new(),
+        Err(e) => return Err(e.into()),
+    };
+    Ok(content.parse()?)
+}
+fn write_file(scope: &Scope, doc: &toml_edit::DocumentMut) -> Result<(), ConfigError> {
+    scope.check(doc)?;
+    let file = scope.path();
+    trace!("WRITING CONFIG {:?}", file);
+    fs::write(file, doc.to_string())?;
+    Ok(())
+}
+/// Key of the line at `offset` of the file, for the errors of deserialization
+fn key_at(doc: &toml_edit::DocumentMut, offset: usize) -> Option<String> {
+    let content = doc.to_string();
+    let line = content.get(..offset)?.lines().last()?;
+    line.split_once('=').map(|(key, _)| key.trim().to_owned())
+}
+fn invalid(key: &str, reason: &str) -> ConfigError {
+    ConfigError::Invalid {
+        key: key.to_owned(),
+        reason: reason.to_owned(),
+    }
+}
+/// Warns about the keys that are not part of the configuration, usually typos
+fn warn_unknown(source: &Config) {
+    let Ok(toml::Value::Table(known)) = toml::Value::try_from(McwrConfig::default()) else {
+        return;
+    };
+    let Ok(keys) = source.clone().try_deserialize::<HashMap<String, config::Value>>() else {
+        return;
+    };
+    for key in keys.keys() {
+        // the unset optional keys are not serialized
+        if !known.contains_key(key) && key != "preset" {
+            warn!("UNKNOWN CONFIG KEY {}", key);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn set_and_unset_keep_the_file() {
+        let root = env::temp_dir().join(format!("mcwr-mconf-{}", std::process::id()));
+        fs::create_dir_all(&root).unwrap();
+        let dirs = mdirs::init(Some(root.clone())).unwrap();
+        fs::write(dirs.config_file(), "# descargas a la vez\nmax_current_downloads = 5  # por defecto\n").unwrap();
+
+        set(&Scope::Global, "max_current_downloads", "8").unwrap();
+        set(&Scope::Global, "data.auth_player_name", "steve").unwrap();
+        let content = fs::read_to_string(dirs.config_file()).unwrap();
+        assert!(content.starts_with("# descargas a la vez\nmax_current_downloads = 8  # por defecto\n"));
+        assert!(content.contains("[data]\nauth_player_name = \"steve\"\n"));
+
+        assert!(matches!(set(&Scope::Global, "max_current_downloads", "many"), Err(ConfigError::Invalid { .. })));
+        assert!(matches!(set(&Scope::Global, "no_such_key", "1"), Err(ConfigError::UnknownKey(_))));
+
+        assert!(unset(&Scope::Global, "data.auth_player_name").unwrap());
+        assert!(!unset(&Scope::Global, "data.auth_player_name").unwrap());
+        assert!(!fs::read_to_string(dirs.config_file()).unwrap().contains("steve"));
+        fs::remove_dir_all(root).unwrap();
     }
 }
@@ -0,0 +1,271 @@
+use std::collections::HashSet;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+use log::{trace, warn};
+
+use crate::mvers::Version;
+use crate::{mdirs, mlogs};
+
+static CRASH_REPORTS_DIR: &str = "crash-reports";
+static JVM_ERROR_PREFIX: &str = "hs_err_pid";
+/// A report may be written after the last line of the session log
+const SESSION_SLACK: Duration = Duration::from_secs(60);
+
+/// Kind of crash report
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CrashKind {
+    /// Report written by the game in `crash-reports/`
+    Game,
+    /// Fatal error log written by the JVM, `hs_err_pid*.log`
+    Jvm,
+}
+
+/// Summary of a crash report
+#[derive(Debug, Clone)]
+pub struct CrashReport {
+    pub path: PathBuf,
+    pub kind: CrashKind,
+    pub time: SystemTime,
+    /// Process that crashed, only known for the JVM fatal error logs
+    pub pid: Option<u32>,
+    pub minecraft_version: Option<String>,
+    pub exception: Option<String>,
+    pub suspected_mods: Option<String>,
+    pub java_version: Option<String>,
+    pub memory: Option<String>,
+}
+impl CrashReport {
+    /// Reads the report in `path`
+    pub fn read(path: &Path, kind: CrashKind) -> io::Result<CrashReport> {
+        trace!("READING CRASH REPORT {:?}", path);
+        let content = fs::read_to_string(path)?;
+        let time = path.metadata()?.modified()?;
+        let mut report = CrashReport {
+            path: path.to_path_buf(),
+            kind,
+            time,
+            pid: None,
+            minecraft_version: None,
+            exception: None,
+            suspected_mods: None,
+            java_version: None,
+            memory: None,
+        };
+        match kind {
+            CrashKind::Game => {
+                report.minecraft_version = detail(&content, "Minecraft Version:");
+                report.suspected_mods = detail(&content, "Suspected Mod");
+                report.java_version = detail(&content, "Java Version:");
+                report.memory = detail(&content, "Memory:");
+                // the exception is the first line after the description
+                report.exception = content
+                    .lines()
+                    .skip_while(|line| !line.starts_with("Description:"))
+                    .skip(1)
+                    .map(str::trim)
+                    .find(|line| !line.is_empty())
+                    .map(str::to_owned);
+            }
+            CrashKind::Jvm => {
+                report.pid = path
+                    .file_name()
+                    .and_then(|name| name.to_str())
+                    .and_then(|name| name.strip_prefix(JVM_ERROR_PREFIX))
+                    .and_then(|name| name.strip_suffix(".log"))
+                    .and_then(|pid| pid.parse().ok());
+                report.exception = content
+                    .lines()
+                    .find(|line| line.starts_with("#  SIG") || line.starts_with("#  EXCEPTION_"))
+                    .map(|line| line.trim_start_matches('#').trim().to_owned());
+                report.suspected_mods = content
+                    .lines()
+                    .skip_while(|line| !line.starts_with("# Problematic frame:"))
+                    .nth(1)
+                    .map(|line| line.trim_start_matches('#').trim().to_owned());
+                report.java_version = detail(&content, "# JRE version:");
+                report.memory = content
+                    .lines()
+                    .find(|line| line.starts_with("Memory:"))
+                    .map(|line| line.trim_start_matches("Memory:").trim().to_owned());
+                report.minecraft_version = content
+                    .lines()
+                    .find(|line| line.starts_with("java_command:"))
+                    .and_then(|line| line.split_whitespace().skip_while(|arg| *arg != "--version").nth(1))
+                    .map(str::to_owned);
+            }
+        }
+        Ok(report)
+    }
+    /// Whether the report was written by `version`, which ran in the time spans of `sessions`.
+    /// The game directory is shared, so the reports of other versions are found there as well
+    fn belongs_to(&self, version: &str, sessions: &[(SystemTime, SystemTime)]) -> bool {
+        self.minecraft_version.as_deref() == Some(version)
+            || sessions
+                .iter()
+                .any(|(started, ended)| self.time >= *started && self.time <= *ended + SESSION_SLACK)
+    }
+}
+impl fmt::Display for CrashReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let unknown = String::from("unknown");
+        let (title, suspect) = match self.kind {
+            CrashKind::Game => ("Crash report", "Suspected mods"),
+            CrashKind::Jvm => ("JVM fatal error", "Problematic frame"),
+        };
+        writeln!(f, "{}: {}", title, self.path.display())?;
+        writeln!(f, "  Exception: {}", self.exception.as_ref().unwrap_or(&unknown))?;
+        writeln!(f, "  {}: {}", suspect, self.suspected_mods.as_ref().unwrap_or(&unknown))?;
+        writeln!(f, "  Java: {}", self.java_version.as_ref().unwrap_or(&unknown))?;
+        write!(f, "  Memory: {}", self.memory.as_ref().unwrap_or(&unknown))
+    }
+}
+
+/// Watches the crash reports written while a version runs
+pub struct CrashWatch {
+    version: String,
+    game_dir: PathBuf,
+    before: HashSet<PathBuf>,
+}
+impl CrashWatch {
+    /// Takes note of the reports already present before launching `version`
+    pub fn new(version: &Version) -> CrashWatch {
+        let game_dir = version.game_dir();
+        let before = reports(&game_dir)
+            .into_iter()
+            .map(|(path, _)| path)
+            .collect();
+        CrashWatch {
+            version: version.version.clone(),
+            game_dir,
+            before,
+        }
+    }
+    /// Reports written since the watch was created by the game with `pid`
+    pub fn check(&self, pid: u32) -> Vec<CrashReport> {
+        trace!("CHECKING THE CRASHES OF {} ({})", self.version, pid);
+        reports(&self.game_dir)
+            .into_iter()
+            .filter(|(path, _)| !self.before.contains(path))
+            .filter_map(|(path, kind)| read_or_warn(&path, kind))
+            .filter(|report| report.pid.is_none_or(|report| report == pid))
+            .collect()
+    }
+}
+
+/// Crash reports of `version`, the most recent first
+pub fn list(version: &Version) -> Vec<CrashReport> {
+    let sessions: Vec<(SystemTime, SystemTime)> = match mlogs::sessions(&version.version) {
+        Ok(sessions) => sessions.iter().filter_map(|session| mlogs::window(session)).collect(),
+        Err(e) => {
+            warn!("CANNOT READ THE SESSIONS OF {}: {}", version.version, e);
+            Vec::new()
+        }
+    };
+    let mut reports: Vec<CrashReport> = reports(&version.game_dir())
+        .into_iter()
+        .filter_map(|(path, kind)| read_or_warn(&path, kind))
+        .filter(|report| report.belongs_to(&version.version, &sessions))
+        .collect();
+    reports.sort_by_key(|report| std::cmp::Reverse(report.time));
+    reports
+}
+
+/// Paths of the crash reports of a game directory. The JVM writes its error logs in the
+/// working directory, so the data directory the games run in is searched as well
+fn reports(game_dir: &Path) -> Vec<(PathBuf, CrashKind)> {
+    let mut reports = Vec::new();
+    let crash_dir = game_dir.join(CRASH_REPORTS_DIR);
+    for path in files(&crash_dir) {
+        if path.extension().is_some_and(|ext| ext == "txt") {
+            reports.push((path, CrashKind::Game));
+        }
+    }
+    for dir in [game_dir, mdirs::get().data.as_path()] {
+        for path in files(dir) {
+            let is_jvm_error = path
+                .file_name()
+                .and_then(|name| name.to_str())
+                .is_some_and(|name| name.starts_with(JVM_ERROR_PREFIX) && name.ends_with(".log"));
+            if is_jvm_error && !reports.iter().any(|(report, _)| same_file(report, &path)) {
+                reports.push((path, CrashKind::Jvm));
+            }
+        }
+    }
+    reports
+}
+fn files(dir: &Path) -> Vec<PathBuf> {
+    match fs::read_dir(dir) {
+        Ok(entries) => entries
+            .filter_map(|entry| entry.ok())
+            .map(|entry| entry.path())
+            .filter(|path| path.is_file())
+            .collect(),
+        Err(_) => Vec::new(),
+    }
+}
+fn same_file(a: &Path, b: &Path) -> bool {
+    match (a.canonicalize(), b.canonicalize()) {
+        (Ok(a), Ok(b)) => a == b,
+        _ => a == b,
+    }
+}
+fn read_or_warn(path: &Path, kind: CrashKind) -> Option<CrashReport> {
+    match CrashReport::read(path, kind) {
+        Ok(report) => Some(report),
+        Err(e) => {
+            warn!("CANNOT READ CRASH REPORT {:?}: {}", path, e);
+            None
+        }
+    }
+}
+/// Value of the first line starting with `key`, after the colon
+fn detail(content: &str, key: &str) -> Option<String> {
+    content
+        .lines()
+        .map(str::trim)
+        .find(|line| line.starts_with(key))
+        .and_then(|line| line.split_once(':'))
+        .map(|(_, value)| value.trim().to_owned())
+        .filter(|value| !value.is_empty())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn report(minecraft_version: Option<&str>, time: SystemTime) -> CrashReport {
+        CrashReport {
+            path: PathBuf::from("crash-reports/crash.txt"),
+            kind: CrashKind::Game,
+            time,
+            pid: None,
+            minecraft_version: minecraft_version.map(str::to_owned),
+            exception: None,
+            suspected_mods: None,
+            java_version: None,
+            memory: None,
+        }
+    }
+
+    #[test]
+    fn belongs_to_the_exact_version() {
+        let now = SystemTime::now();
+        assert!(report(Some("1.20.4"), now).belongs_to("1.20.4", &[]));
+        assert!(!report(Some("1.20"), now).belongs_to("1.20.4", &[]));
+    }
+
+    #[test]
+    fn belongs_to_the_sessions_of_the_version() {
+        let now = SystemTime::now();
+        let sessions = [(now - Duration::from_secs(600), now - Duration::from_secs(300))];
+        // a custom id never matches the vanilla version of the report
+        assert!(report(Some("1.20.4"), now - Duration::from_secs(400)).belongs_to("my-pack", &sessions));
+        assert!(report(None, now - Duration::from_secs(280)).belongs_to("my-pack", &sessions));
+        assert!(!report(Some("1.20.4"), now).belongs_to("my-pack", &sessions));
+        assert!(!report(None, now - Duration::from_secs(700)).belongs_to("my-pack", &sessions));
+    }
+}
@@ -0,0 +1,100 @@
+use std::env;
+use std::fs::{self, File};
+use std::io;
+use std::path::{Path, PathBuf};
+
+use log::{trace, warn};
+
+use crate::{mconf, mdirs};
+use crate::mvers::Version;
+
+/// Icon of the client jar, present since 1.6
+static JAR_ICON: &str = "pack.png";
+
+/// Writes the desktop entry launching `version`, and its icon when the client jar has one.
+/// Returns the path of the entry
+pub fn install(version: &Version) -> io::Result<PathBuf> {
+    let entry = entry_path(&version.version)?;
+    let icon = match extract_icon(version) {
+        Ok(icon) => Some(icon),
+        Err(e) => {
+            warn!("CANNOT EXTRACT ICON OF {}: {}", version.version, e);
+            None
+        }
+    };
+    let dirs = mdirs::get();
+    let mut mcwr = quote(&env::current_exe()?.to_string_lossy());
+    // a portable installation is only found through its root
+    if dirs.portable {
+        mcwr = format!("{} --root {}", mcwr, quote(&dirs.data.to_string_lossy()));
+    }
+    let mut content = format!(
+        "[Desktop Entry]\nType=Application\nName=Minecraft {}\nComment=Launch Minecraft {} with mcwr\nExec={} run {}\nPath={}\nTerminal=false\nCategories=Game;\n",
+        version.version,
+        version.version,
+        mcwr,
+        quote(&version.version),
+        dirs.data.display()
+    );
+    if let Some(icon) = icon {
+        content.push_str(&format!("Icon={}\n", icon.display()));
+    }
+    if let Some(parent) = entry.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    trace!("WRITING DESKTOP ENTRY {:?}", entry);
+    fs::write(&entry, content)?;
+    Ok(entry)
+}
+
+/// Removes the desktop entry of `version` and its icon, returns whether the entry existed
+pub fn remove(version: &str) -> io::Result<bool> {
+    let icon = icon_path(version)?;
+    if icon.exists() {
+        fs::remove_file(icon)?;
+    }
+    let entry = entry_path(version)?;
+    if !entry.exists() {
+        return Ok(false);
+    }
+    trace!("REMOVING DESKTOP ENTRY {:?}", entry);
+    fs::remove_file(entry)?;
+    Ok(true)
+}
+
+fn extract_icon(version: &Version) -> io::Result<PathBuf> {
+    let jar = format!("{}/{}/{}.jar", mconf::config().versions, version.version, version.version);
+    let mut jar = zip::ZipArchive::new(File::open(jar)?).map_err(io::Error::other)?;
+    let mut icon = jar.by_name(JAR_ICON).map_err(io::Error::other)?;
+    let path = icon_path(&version.version)?;
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    io::copy(&mut icon, &mut File::create(&path)?)?;
+    Ok(path)
+}
+fn entry_path(version: &str) -> io::Result<PathBuf> {
+    Ok(data_home()?.join("applications").join(format!("mcwr-{}.desktop", version)))
+}
+fn icon_path(version: &str) -> io::Result<PathBuf> {
+    Ok(data_home()?.join("icons").join(format!("mcwr-{}.png", version)))
+}
+fn data_home() -> io::Result<PathBuf> {
+    if let Some(data) = env::var_os("XDG_DATA_HOME").filter(|data| !data.is_empty()) {
+        return Ok(PathBuf::from(data));
+    }
+    let home = env::var_os("HOME").ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "HOME is not set"))?;
+    Ok(Path::new(&home).join(".local/share"))
+}
+/// Quotes an argument of the `Exec` key when it has reserved characters
+fn quote(arg: &str) -> String {
+    if !arg.contains(|c: char| c.is_whitespace() || "\"'\\><~|&;$*?#()`".contains(c)) {
+        return arg.to_owned();
+    }
+    let escaped = arg
+        .replace('\\', "\\\\\\\\")
+        .replace('"', "\\\\\"")
+        .replace('`', "\\\\`")
+        .replace('$', "\\\\$");
+    format!("\"{}\"", escaped)
+}
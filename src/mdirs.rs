@@ -0,0 +1,174 @@
+use std::env;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+use log::{trace, warn};
+
+static APP_DIR: &str = "mcwr";
+static CONFIG_FILE: &str = "mcwr.conf";
+/// Root of the portable mode, everything is kept inside it
+static HOME_ENV: &str = "MCWR_HOME";
+static DIRS: OnceLock<Dirs> = OnceLock::new();
+
+/// Where mcwr keeps its files
+#[derive(Debug, Clone)]
+pub struct Dirs {
+    /// Directory of `mcwr.conf`
+    pub config: PathBuf,
+    /// Base of the relative data paths of the configuration: versions, assets, runtimes...
+    pub data: PathBuf,
+    /// Base of the relative cache paths of the configuration: manifest and temporary files
+    pub cache: PathBuf,
+    /// Given with `--root` or `MCWR_HOME`
+    pub portable: bool,
+}
+impl Dirs {
+    pub fn config_file(&self) -> PathBuf {
+        self.config.join(CONFIG_FILE)
+    }
+}
+
+/// Resolves the directories, in `root` or `MCWR_HOME` if given, in the XDG directories otherwise
+pub fn init(root: Option<PathBuf>) -> io::Result<&'static Dirs> {
+    if let Some(dirs) = DIRS.get() {
+        return Ok(dirs);
+    }
+    let root = root.or_else(|| env::var_os(HOME_ENV).filter(|home| !home.is_empty()).map(PathBuf::from));
+    let dirs = match root {
+        Some(root) => {
+            let root = env::current_dir()?.join(root);
+            let root = root.canonicalize().unwrap_or(root);
+            Dirs {
+                config: root.clone(),
+                data: root.clone(),
+                cache: root,
+                portable: true,
+            }
+        }
+        None => Dirs {
+            config: xdg("XDG_CONFIG_HOME", ".config")?.join(APP_DIR),
+            data: xdg("XDG_DATA_HOME", ".local/share")?.join(APP_DIR),
+            cache: xdg("XDG_CACHE_HOME", ".cache")?.join(APP_DIR),
+            portable: false,
+        },
+    };
+    trace!("MCWR DIRS {:?}", dirs);
+    Ok(DIRS.get_or_init(|| dirs))
+}
+/// devuelve los directorios resueltos al iniciar, `init` se llama antes de cualquier comando
+pub fn get() -> &'static Dirs {
+    DIRS.get().expect("MCWR DIRS USED BEFORE INIT")
+}
+
+/// Moves the layout of the older versions, kept in the working directory, to the XDG
+/// directories. Returns whether there was something to move
+pub fn migrate() -> io::Result<bool> {
+    let dirs = get();
+    let cwd = env::current_dir()?;
+    let old_config = cwd.join(CONFIG_FILE);
+    if dirs.portable || dirs.config_file().exists() || !old_config.exists() {
+        return Ok(false);
+    }
+    trace!("MIGRATING LAYOUT OF {:?}", cwd);
+    let old: toml::Table = fs::read_to_string(&old_config)?.parse().map_err(io::Error::other)?;
+    let value = |key: &str, default: &str| old.get(key).and_then(|v| v.as_str()).unwrap_or(default).to_owned();
+    let mut moves = Vec::new();
+    for (key, default) in [("versions", "versions"), ("resources", "assets"), ("java", "java"), ("pwd", "workdir"), ("export_path", "exports")] {
+        moves.push((value(key, default), &dirs.data));
+    }
+    moves.push((value("manifest", "manifest.json"), &dirs.cache));
+    let mut moved = Vec::new();
+    for (path, base) in moves {
+        let from = Path::new(&path);
+        if from.is_absolute() || !from.exists() {
+            continue;
+        }
+        let to = base.join(from);
+        if to.exists() {
+            warn!("{:?} ALREADY EXISTS, {:?} IS NOT MOVED", to, from);
+            continue;
+        }
+        eprintln!("Moving {} to {}", from.display(), to.display());
+        move_path(&cwd.join(from), &to)?;
+        moved.push((cwd.join(from).display().to_string(), to.display().to_string()));
+    }
+    // the metadata of the versions may keep absolute paths of the moved directories
+    let versions = dirs.data.join(value("versions", "versions"));
+    if let Ok(entries) = fs::read_dir(&versions) {
+        for entry in entries {
+            let meta = entry?.path().join(".info");
+            let Ok(mut content) = fs::read_to_string(&meta) else {
+                continue;
+            };
+            trace!("REWRITING PATHS OF {:?}", meta);
+            for (from, to) in &moved {
+                content = content
+                    .replace(&format!("{}/", from), &format!("{}/", to))
+                    .replace(&format!("\"{}\"", from), &format!("\"{}\"", to));
+            }
+            fs::write(&meta, content)?;
+        }
+    }
+    fs::create_dir_all(&dirs.config)?;
+    eprintln!("Moving {} to {}", CONFIG_FILE, dirs.config_file().display());
+    move_path(&old_config, &dirs.config_file())?;
+    Ok(true)
+}
+
+fn xdg(var: &str, fallback: &str) -> io::Result<PathBuf> {
+    if let Some(dir) = env::var_os(var).filter(|dir| !dir.is_empty()) {
+        return Ok(PathBuf::from(dir));
+    }
+    let home = env::var_os("HOME").ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "HOME is not set"))?;
+    Ok(Path::new(&home).join(fallback))
+}
+/// Renames `from` to `to`, copying it when they are in different filesystems
+pub(crate) fn move_path(from: &Path, to: &Path) -> io::Result<()> {
+    if let Some(parent) = to.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    match fs::rename(from, to) {
+        Err(e) if e.kind() == io::ErrorKind::CrossesDevices => {
+            trace!("COPYING {:?} TO {:?}", from, to);
+            copy_path(from, to)?;
+            if from.is_dir() {
+                fs::remove_dir_all(from)
+            } else {
+                fs::remove_file(from)
+            }
+        }
+        result => result,
+    }
+}
+pub(crate) fn copy_path(from: &Path, to: &Path) -> io::Result<()> {
+    let meta = fs::symlink_metadata(from)?;
+    if meta.is_symlink() {
+        #[cfg(unix)]
+        std::os::unix::fs::symlink(fs::read_link(from)?, to)?;
+        return Ok(());
+    }
+    if !meta.is_dir() {
+        fs::copy(from, to)?;
+        return Ok(());
+    }
+    fs::create_dir_all(to)?;
+    for entry in fs::read_dir(from)? {
+        let entry = entry?;
+        copy_path(&entry.path(), &to.join(entry.file_name()))?;
+    }
+    Ok(())
+}
+/// Size on disk of `path`, the symlinks are not followed
+pub(crate) fn size_of(path: &Path) -> io::Result<u64> {
+    let meta = fs::symlink_metadata(path)?;
+    if !meta.is_dir() {
+        return Ok(meta.len());
+    }
+    let mut size = 0;
+    for entry in fs::read_dir(path)? {
+        size += size_of(&entry?.path())?;
+    }
+    Ok(size)
+}
@@ -0,0 +1,158 @@
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+
+static DEFAULT_PORT: &str = "25565";
+static QUICK_PLAY_LOG: &str = "quickPlay/log.json";
+
+/// Where the game goes right after starting
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum QuickPlay {
+    /// Multiplayer server, `host[:port]`
+    Server(String),
+    /// Singleplayer world, by the name of its folder
+    World(String),
+    /// Realm, by its id
+    Realm(String),
+}
+impl QuickPlay {
+    /// What the game opens
+    pub fn target(&self) -> &'static str {
+        match self {
+            QuickPlay::Server(_) => "server",
+            QuickPlay::World(_) => "world",
+            QuickPlay::Realm(_) => "realm",
+        }
+    }
+    /// Placeholder of the client JSON arguments that take the target
+    pub fn placeholder(&self) -> &'static str {
+        match self {
+            QuickPlay::Server(_) => "${quickPlayMultiplayer}",
+            QuickPlay::World(_) => "${quickPlaySingleplayer}",
+            QuickPlay::Realm(_) => "${quickPlayRealms}",
+        }
+    }
+}
+
+/// Size of the game window
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Resolution {
+    pub width: u32,
+    pub height: u32,
+}
+
+/// Launch features, they enable the rule-gated arguments of the client JSON
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Features {
+    /// Only given per run, a saved one would open the same place on every launch
+    #[serde(skip)]
+    pub quick_play: Option<QuickPlay>,
+    pub resolution: Option<Resolution>,
+    pub fullscreen: Option<bool>,
+    pub demo: Option<bool>,
+}
+impl Features {
+    /// Features of a run over these defaults
+    pub fn with(&self, run: &Features) -> Features {
+        Features {
+            quick_play: run.quick_play.clone().or_else(|| self.quick_play.clone()),
+            resolution: run.resolution.or(self.resolution),
+            fullscreen: run.fullscreen.or(self.fullscreen),
+            demo: run.demo.or(self.demo),
+        }
+    }
+    /// Replaces these defaults with the features given in `run`, but the quick play
+    pub fn update(&mut self, run: &Features) {
+        if run.resolution.is_some() {
+            self.resolution = run.resolution;
+        }
+        if run.fullscreen.is_some() {
+            self.fullscreen = run.fullscreen;
+        }
+        if run.demo.is_some() {
+            self.demo = run.demo;
+        }
+    }
+    pub fn is_empty(&self) -> bool {
+        self.quick_play.is_none()
+            && self.resolution.is_none()
+            && self.fullscreen.is_none()
+            && self.demo.is_none()
+    }
+    /// Feature flags evaluated by the rules of the client JSON
+    pub fn options(&self) -> HashMap<String, bool> {
+        let mut options = HashMap::new();
+        if self.resolution.is_some() {
+            options.insert(String::from("has_custom_resolution"), true);
+        }
+        if self.demo == Some(true) {
+            options.insert(String::from("is_demo_user"), true);
+        }
+        if let Some(quick_play) = &self.quick_play {
+            let feature = match quick_play {
+                QuickPlay::Server(_) => "is_quick_play_multiplayer",
+                QuickPlay::World(_) => "is_quick_play_singleplayer",
+                QuickPlay::Realm(_) => "is_quick_play_realms",
+            };
+            options.insert(String::from("has_quick_plays_support"), true);
+            options.insert(feature.to_owned(), true);
+        }
+        options
+    }
+    /// Placeholders used by the arguments the features enable
+    pub fn data(&self) -> HashMap<String, String> {
+        let mut data = HashMap::new();
+        if let Some(resolution) = self.resolution {
+            data.insert(String::from("resolution_width"), resolution.width.to_string());
+            data.insert(String::from("resolution_height"), resolution.height.to_string());
+        }
+        if let Some(quick_play) = &self.quick_play {
+            let (key, value) = match quick_play {
+                QuickPlay::Server(server) => ("quickPlayMultiplayer", server),
+                QuickPlay::World(world) => ("quickPlaySingleplayer", world),
+                QuickPlay::Realm(realm) => ("quickPlayRealms", realm),
+            };
+            data.insert(key.to_owned(), value.clone());
+            data.insert(String::from("quickPlayPath"), QUICK_PLAY_LOG.to_owned());
+        }
+        data
+    }
+    /// Game arguments of the features that no client JSON declares
+    pub fn args(&self) -> Vec<String> {
+        let mut args = Vec::new();
+        if self.fullscreen == Some(true) {
+            args.push(String::from("--fullscreen"));
+        }
+        args
+    }
+    /// Game arguments of the features for versions whose client JSON has no rule-gated arguments
+    pub fn legacy_args(&self) -> Vec<String> {
+        let mut args = Vec::new();
+        if let Some(resolution) = self.resolution {
+            args.extend([
+                String::from("--width"),
+                resolution.width.to_string(),
+                String::from("--height"),
+                resolution.height.to_string(),
+            ]);
+        }
+        if self.demo == Some(true) {
+            args.push(String::from("--demo"));
+        }
+        args
+    }
+    /// Game arguments of the quick play for versions without it, only a server can be joined
+    /// with them, `None` if the quick play has no legacy arguments
+    pub fn legacy_quick_play(&self) -> Option<Vec<String>> {
+        match &self.quick_play {
+            Some(QuickPlay::Server(server)) => {
+                let (host, port) = server.split_once(':').unwrap_or((server, DEFAULT_PORT));
+                Some(["--server", host, "--port", port].map(str::to_owned).to_vec())
+            }
+            Some(_) => None,
+            None => Some(Vec::new()),
+        }
+    }
+}
@@ -0,0 +1,171 @@
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use log::trace;
+use mcd::api::ApiClientError;
+use serde_json::{Map, Value};
+
+use crate::errors::McwrError;
+use crate::{mconf, mdirs, mvers, temp};
+
+/// Directory of the data directory with the parents of custom JSONs, as `<id>.json` or `<id>/<id>.json`
+static CUSTOM_DIR: &str = "custom-versions";
+
+/// Reads the client JSON in `path` and merges it with the chain of versions it inherits from
+pub fn resolve(path: &Path) -> Result<Value, McwrError> {
+    let mut client = read(path, &path.to_string_lossy())?;
+    let id = client.get("id").and_then(Value::as_str).unwrap_or_default().to_owned();
+    // the merged client keeps the id of the child, the chain is followed by the parents
+    let mut seen = HashSet::from([id.clone()]);
+    let mut child = id.clone();
+    while let Some(parent) = client.get("inheritsFrom").and_then(Value::as_str).map(str::to_owned) {
+        if !seen.insert(parent.clone()) {
+            return Err(McwrError::InheritanceCycle(id));
+        }
+        trace!("{} INHERITS FROM {}", child, parent);
+        client = merge(parent_of(&child, &parent)?, client);
+        child = parent;
+    }
+    Ok(client)
+}
+
+/// Client JSON of `parent`, from `custom-versions/`, the installed versions or the manifest
+fn parent_of(version: &str, parent: &str) -> Result<Value, McwrError> {
+    let custom = mdirs::get().data.join(CUSTOM_DIR);
+    let installed = Path::new(&mconf::config().versions).join(parent).join(format!("{}.json", parent));
+    let local = [
+        custom.join(format!("{}.json", parent)),
+        custom.join(parent).join(format!("{}.json", parent)),
+        installed,
+    ];
+    if let Some(path) = local.iter().find(|path| path.exists()) {
+        trace!("PARENT {} FOUND IN {:?}", parent, path);
+        return read(path, parent);
+    }
+    let tmp = PathBuf::from(temp!("mcwr-parent.tmp"));
+    match mvers::fetch(parent, &tmp.to_string_lossy()) {
+        Ok(_) => read(&tmp, parent),
+        Err(McwrError::UnknownVersion(_)) => Err(McwrError::MissingParent {
+            version: version.to_owned(),
+            parent: parent.to_owned(),
+        }),
+        Err(e) => Err(e),
+    }
+}
+
+fn read(path: &Path, version: &str) -> Result<Value, McwrError> {
+    let client = |source| McwrError::Client { version: version.to_owned(), source };
+    let content = fs::read_to_string(path).map_err(|e| client(ApiClientError::IOError(e)))?;
+    serde_json::from_str(&content).map_err(|e| client(ApiClientError::JsonError(e)))
+}
+
+/// Merges `child` over `parent` like the official launcher: the values of the child replace the
+/// ones of the parent, the arguments are appended and the libraries of the child go first,
+/// replacing the libraries of the parent with the same artifact
+fn merge(parent: Value, child: Value) -> Value {
+    let (Value::Object(mut merged), Value::Object(child)) = (parent, child) else {
+        return Value::Null;
+    };
+    for (key, value) in child {
+        match key.as_str() {
+            // the chain continues with the parent of the parent
+            "inheritsFrom" => {}
+            "libraries" => {
+                let parent = merged.remove("libraries").unwrap_or(Value::Array(Vec::new()));
+                merged.insert(key, merge_libraries(parent, value));
+            }
+            "arguments" => {
+                let parent = merged.remove("arguments").unwrap_or(Value::Object(Map::new()));
+                merged.insert(key, merge_arguments(parent, value));
+            }
+            _ => {
+                merged.insert(key, value);
+            }
+        }
+    }
+    Value::Object(merged)
+}
+fn merge_libraries(parent: Value, child: Value) -> Value {
+    let (Value::Array(parent), Value::Array(mut libraries)) = (parent, child) else {
+        return Value::Array(Vec::new());
+    };
+    let artifacts: HashSet<String> = libraries.iter().filter_map(artifact).collect();
+    libraries.extend(parent.into_iter().filter(|library| artifact(library).is_none_or(|key| !artifacts.contains(&key))));
+    Value::Array(libraries)
+}
+fn merge_arguments(parent: Value, child: Value) -> Value {
+    let (Value::Object(mut merged), Value::Object(child)) = (parent, child) else {
+        return Value::Object(Map::new());
+    };
+    for (key, value) in child {
+        match (merged.get_mut(&key), value) {
+            (Some(Value::Array(arguments)), Value::Array(extra)) => arguments.extend(extra),
+            (_, value) => {
+                merged.insert(key, value);
+            }
+        }
+    }
+    Value::Object(merged)
+}
+/// `group:artifact[:classifier]` of a library, its name without the version
+fn artifact(library: &Value) -> Option<String> {
+    let name = library.get("name")?.as_str()?;
+    let parts: Vec<&str> = name.split(':').collect();
+    match parts.as_slice() {
+        [group, artifact, _] => Some(format!("{}:{}", group, artifact)),
+        [group, artifact, _, classifier, ..] => Some(format!("{}:{}:{}", group, artifact, classifier)),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn merges_the_child_over_the_parent() {
+        let parent = json!({
+            "id": "1.20.4",
+            "mainClass": "net.minecraft.client.main.Main",
+            "arguments": { "game": ["--username", "${auth_player_name}"], "jvm": ["-cp", "${classpath}"] },
+            "libraries": [
+                { "name": "org.ow2.asm:asm:9.3" },
+                { "name": "com.mojang:brigadier:1.2.9" },
+            ],
+        });
+        let child = json!({
+            "id": "fabric-loader-0.15.0-1.20.4",
+            "inheritsFrom": "1.20.4",
+            "mainClass": "net.fabricmc.loader.impl.launch.knot.KnotClient",
+            "arguments": { "jvm": ["-DFabricMcEmu= net.minecraft.client.main.Main "] },
+            "libraries": [{ "name": "org.ow2.asm:asm:9.6" }],
+        });
+        let merged = merge(parent, child);
+        assert_eq!(merged["id"], "fabric-loader-0.15.0-1.20.4");
+        assert_eq!(merged["mainClass"], "net.fabricmc.loader.impl.launch.knot.KnotClient");
+        // the parent of the chain is resolved by `resolve`, not copied from the child
+        assert!(merged.get("inheritsFrom").is_none());
+        assert_eq!(merged["arguments"]["game"], json!(["--username", "${auth_player_name}"]));
+        assert_eq!(
+            merged["arguments"]["jvm"],
+            json!(["-cp", "${classpath}", "-DFabricMcEmu= net.minecraft.client.main.Main "])
+        );
+        assert_eq!(
+            merged["libraries"],
+            json!([{ "name": "org.ow2.asm:asm:9.6" }, { "name": "com.mojang:brigadier:1.2.9" }])
+        );
+    }
+
+    #[test]
+    fn artifact_ignores_the_version() {
+        assert_eq!(artifact(&json!({ "name": "org.lwjgl:lwjgl:3.3.1" })).as_deref(), Some("org.lwjgl:lwjgl"));
+        assert_eq!(
+            artifact(&json!({ "name": "org.lwjgl:lwjgl:3.3.1:natives-linux" })).as_deref(),
+            Some("org.lwjgl:lwjgl:natives-linux")
+        );
+        assert_eq!(artifact(&json!({ "name": "broken" })), None);
+    }
+}
@@ -0,0 +1,332 @@
+use std::collections::{HashMap, HashSet};
+use std::env;
+use std::fmt;
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+use dwldutil::{DLFile, DLHashType};
+use log::{trace, warn};
+use serde::Serialize;
+use mcd::errors::FetchError;
+use mcd::java::JavaUtil;
+
+use crate::errors::McwrError;
+use crate::{mconf, mdirs};
+use crate::mvers::{Version, JAVA_BIN};
+
+static SYSTEM_JVMS: &str = "/usr/lib/jvm";
+static RELEASE_FILE: &str = "release";
+/// Extension of the hashes of the files of a runtime, written next to it in `sha1sum` format
+static MANIFEST_EXT: &str = "sha1";
+/// Value of `--java` choosing an installed Java by the version it needs
+pub static AUTO: &str = "auto";
+
+/// A Java installation found in the system
+#[derive(Debug, Clone)]
+pub struct JavaInstall {
+    pub home: PathBuf,
+    pub bin: PathBuf,
+    /// Full version, as written in the `release` file
+    pub version: Option<String>,
+    pub major: Option<usize>,
+    pub vendor: Option<String>,
+}
+impl JavaInstall {
+    /// Reads the installation in `home`, None if it has no java binary
+    pub fn from_home(home: &Path) -> Option<JavaInstall> {
+        let bin = home.join("bin").join(JAVA_BIN);
+        if !bin.is_file() {
+            return None;
+        }
+        let release = fs::read_to_string(home.join(RELEASE_FILE)).unwrap_or_default();
+        let version = release_value(&release, "JAVA_VERSION");
+        Some(JavaInstall {
+            home: home.to_path_buf(),
+            bin,
+            major: version.as_deref().and_then(major_of),
+            version,
+            vendor: release_value(&release, "IMPLEMENTOR"),
+        })
+    }
+    /// Reads the installation of a java binary or a Java home
+    pub fn from_path(path: &Path) -> Option<JavaInstall> {
+        let path = path.canonicalize().ok()?;
+        if path.is_dir() {
+            return JavaInstall::from_home(&path);
+        }
+        // <home>/bin/java
+        JavaInstall::from_home(path.parent()?.parent()?)
+    }
+}
+impl fmt::Display for JavaInstall {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(vendor) = &self.vendor {
+            write!(f, "{} ", vendor)?;
+        }
+        write!(f, "{} ({})", self.version.as_deref().unwrap_or("unknown"), self.home.display())
+    }
+}
+
+/// Java installations of JAVA_HOME, PATH and the system JVM directory
+pub fn discover() -> Vec<JavaInstall> {
+    let mut homes = Vec::new();
+    if let Some(home) = env::var_os("JAVA_HOME").filter(|home| !home.is_empty()) {
+        homes.push(PathBuf::from(home));
+    }
+    if let Some(path) = env::var_os("PATH") {
+        for dir in env::split_paths(&path) {
+            let bin = dir.join(JAVA_BIN);
+            // the java of PATH is usually a chain of links to the real installation
+            if let Some(home) = bin.canonicalize().ok().and_then(|bin| Some(bin.parent()?.parent()?.to_path_buf())) {
+                homes.push(home);
+            }
+        }
+    }
+    if let Ok(entries) = fs::read_dir(SYSTEM_JVMS) {
+        homes.extend(entries.filter_map(|entry| entry.ok()).map(|entry| entry.path()));
+    }
+    let mut seen = HashSet::new();
+    let mut installs = Vec::new();
+    for home in homes {
+        let Ok(home) = home.canonicalize() else {
+            continue;
+        };
+        if !seen.insert(home.clone()) {
+            continue;
+        }
+        if let Some(install) = JavaInstall::from_home(&home) {
+            trace!("FOUND JAVA {:?}", install);
+            installs.push(install);
+        }
+    }
+    installs
+}
+
+/// Installed Java that best fits `major`: the same major version, or else the oldest newer one
+pub fn auto(major: usize) -> Option<JavaInstall> {
+    let installs = discover();
+    let exact = installs.iter().find(|install| install.major == Some(major));
+    exact
+        .or_else(|| {
+            installs
+                .iter()
+                .filter(|install| install.major.is_some_and(|found| found > major))
+                .min_by_key(|install| install.major)
+        })
+        .cloned()
+}
+
+/// Binary of the Java chosen with `--java`, `auto` or a path, for a version needing `major`.
+/// Warns when the chosen Java does not satisfy the version
+pub fn select(java: &str, major: usize) -> Result<PathBuf, McwrError> {
+    let install = if java == AUTO {
+        auto(major).ok_or_else(|| McwrError::NoJava(format!("an installed Java {}", major)))?
+    } else {
+        JavaInstall::from_path(Path::new(java)).ok_or_else(|| McwrError::NoJava(format!("a Java in `{}`", java)))?
+    };
+    match install.major {
+        Some(found) if found < major => warn!("JAVA {} IS OLDER THAN THE JAVA {} REQUIRED", install, major),
+        Some(found) if found != major => warn!("JAVA {} IS NOT THE JAVA {} OF THE VERSION", install, major),
+        Some(_) => {}
+        None => warn!("UNKNOWN VERSION OF JAVA {}", install),
+    }
+    trace!("USING JAVA {}", install);
+    Ok(install.bin)
+}
+
+/// A Java runtime downloaded by mcwr in the `java` root
+#[derive(Debug, Clone)]
+pub struct Runtime {
+    pub id: String,
+    pub home: PathBuf,
+    pub major: Option<usize>,
+    /// Size on disk in bytes
+    pub size: u64,
+}
+impl Runtime {
+    /// Versions that run with this runtime
+    pub fn used_by<'a>(&self, versions: impl IntoIterator<Item = &'a Version>) -> Vec<String> {
+        let mut used: Vec<String> = versions
+            .into_iter()
+            .filter(|version| mdirs::get().data.join(&version.java).starts_with(&self.home))
+            .map(|version| version.version.clone())
+            .collect();
+        used.sort();
+        used
+    }
+}
+
+/// A file of a runtime or a version that does not match its hash
+#[derive(Debug, Serialize)]
+#[serde(tag = "state", content = "path", rename_all = "lowercase")]
+pub enum Damage {
+    Missing(String),
+    Modified(String),
+}
+impl fmt::Display for Damage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Damage::Missing(path) => write!(f, "missing: {}", path),
+            Damage::Modified(path) => write!(f, "modified: {}", path),
+        }
+    }
+}
+
+/// Runtimes downloaded in the `java` root
+pub fn runtimes() -> io::Result<Vec<Runtime>> {
+    let root = root();
+    let mut runtimes = Vec::new();
+    let Ok(entries) = fs::read_dir(&root) else {
+        return Ok(runtimes);
+    };
+    for entry in entries {
+        let home = entry?.path();
+        let Some(id) = home.file_name().and_then(|id| id.to_str()).map(str::to_owned) else {
+            continue;
+        };
+        // the downloads in progress are hidden
+        if !home.is_dir() || id.starts_with('.') {
+            continue;
+        }
+        runtimes.push(Runtime {
+            major: JavaInstall::from_home(&home).and_then(|install| install.major),
+            size: mdirs::size_of(&home)?,
+            id,
+            home,
+        });
+    }
+    runtimes.sort_by(|a, b| a.major.cmp(&b.major).then_with(|| a.id.cmp(&b.id)));
+    Ok(runtimes)
+}
+/// Runtime by its id or its major version
+pub fn runtime(target: &str) -> io::Result<Option<Runtime>> {
+    Ok(runtimes()?
+        .into_iter()
+        .find(|runtime| runtime.id == target || runtime.major.is_some_and(|major| major.to_string() == target)))
+}
+
+/// Download of the runtime of Java `major`, None if it is installed already.
+/// The runtime is extracted in a staging directory, [`install`] moves it into place
+pub fn fetch(major: usize) -> Result<Option<DLFile>, FetchError> {
+    let javau = JavaUtil::new();
+    let id = javau.id_of(major).ok_or_else(|| FetchError::UrlNotFound(major.to_string()))?;
+    if root().join(&id).exists() {
+        trace!("RUNTIME {} ALREADY INSTALLED", id);
+        return Ok(None);
+    }
+    let staging = staging(major);
+    // leftovers of an interrupted download
+    if staging.exists() {
+        fs::remove_dir_all(&staging)?;
+    }
+    fs::create_dir_all(root())?;
+    javau.fetch(major, &staging.to_string_lossy()).map(Some)
+}
+/// Moves the runtime of Java `major` downloaded by [`fetch`] into the `java` root and records
+/// the hashes of its files. Returns its home
+pub fn install(major: usize) -> io::Result<PathBuf> {
+    let id = JavaUtil::new()
+        .id_of(major)
+        .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, format!("no runtime of Java {}", major)))?;
+    let home = root().join(&id);
+    let staging = staging(major);
+    if !staging.exists() {
+        if home.exists() {
+            return Ok(home);
+        }
+        return Err(io::Error::new(io::ErrorKind::NotFound, format!("runtime {} was not downloaded", id)));
+    }
+    trace!("MOVING RUNTIME {:?} TO {:?}", staging, home);
+    fs::rename(staging.join(&id), &home)?;
+    fs::remove_dir_all(&staging)?;
+    write_manifest(&home)?;
+    Ok(home)
+}
+/// Removes a runtime and its manifest
+pub fn remove(runtime: &Runtime) -> io::Result<()> {
+    trace!("REMOVING RUNTIME {:?}", runtime.home);
+    fs::remove_dir_all(&runtime.home)?;
+    let manifest = manifest_path(&runtime.home);
+    if manifest.exists() {
+        fs::remove_file(manifest)?;
+    }
+    Ok(())
+}
+/// Checks the files of a runtime against the hashes recorded when it was installed
+pub fn verify(runtime: &Runtime) -> io::Result<Vec<Damage>> {
+    let manifest = fs::read_to_string(manifest_path(&runtime.home)).map_err(|e| {
+        io::Error::new(e.kind(), String::from("no manifest recorded, reinstall the runtime to verify it"))
+    })?;
+    let mut damages = Vec::new();
+    for (hash, file) in manifest.lines().filter_map(|line| line.split_once("  ")) {
+        match fs::read(runtime.home.join(file)) {
+            Ok(data) if DLHashType::SHA1.compute(&data) == hash => {}
+            Ok(_) => damages.push(Damage::Modified(file.to_owned())),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => damages.push(Damage::Missing(file.to_owned())),
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(damages)
+}
+
+fn root() -> PathBuf {
+    PathBuf::from(&mconf::config().java)
+}
+fn staging(major: usize) -> PathBuf {
+    root().join(format!(".java-{}", major))
+}
+fn manifest_path(home: &Path) -> PathBuf {
+    // runtime ids have dots, the extension is appended
+    let mut path = home.as_os_str().to_owned();
+    path.push(".");
+    path.push(MANIFEST_EXT);
+    PathBuf::from(path)
+}
+fn write_manifest(home: &Path) -> io::Result<()> {
+    let mut hashes = HashMap::new();
+    hash_dir(home, home, &mut hashes)?;
+    let mut files: Vec<_> = hashes.into_iter().collect();
+    files.sort();
+    let path = manifest_path(home);
+    trace!("WRITING RUNTIME MANIFEST {:?}", path);
+    let mut manifest = io::BufWriter::new(fs::File::create(path)?);
+    for (file, hash) in files {
+        writeln!(manifest, "{}  {}", hash, file)?;
+    }
+    manifest.flush()
+}
+fn hash_dir(home: &Path, dir: &Path, hashes: &mut HashMap<String, String>) -> io::Result<()> {
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        // links are checked through the files they point to
+        if path.is_symlink() {
+            continue;
+        }
+        if path.is_dir() {
+            hash_dir(home, &path, hashes)?;
+        } else {
+            let file = path.strip_prefix(home).map_err(io::Error::other)?;
+            hashes.insert(file.to_string_lossy().into_owned(), DLHashType::SHA1.compute(&fs::read(&path)?));
+        }
+    }
+    Ok(())
+}
+
+/// Value of `key` in a `release` file, `KEY="value"`
+fn release_value(release: &str, key: &str) -> Option<String> {
+    release
+        .lines()
+        .filter_map(|line| line.split_once('='))
+        .find(|(k, _)| k.trim() == key)
+        .map(|(_, value)| value.trim().trim_matches('"').to_owned())
+        .filter(|value| !value.is_empty())
+}
+/// Major of a Java version, `1.8.0_392` is 8 and `17.0.9` is 17
+fn major_of(version: &str) -> Option<usize> {
+    let mut parts = version.split(['.', '_', '-', '+']);
+    match parts.next()?.parse().ok()? {
+        1 => parts.next()?.parse().ok(),
+        major => Some(major),
+    }
+}
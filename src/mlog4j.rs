@@ -0,0 +1,164 @@
+use std::fmt;
+
+use console::style;
+
+use crate::mvers::OutputLine;
+
+static EVENT_START: &str = "<log4j:Event";
+static EVENT_END: &str = "</log4j:Event>";
+/// Events bigger than this are not log4j output, they are flushed as plain text
+static MAX_EVENT_SIZE: usize = 1024 * 1024;
+
+/// A log event written by the game with the log4j XML layout
+#[derive(Debug, Clone)]
+pub struct LogRecord {
+    pub level: String,
+    pub thread: String,
+    pub logger: String,
+    pub message: String,
+    pub throwable: Option<String>,
+}
+impl fmt::Display for LogRecord {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "[{}/{}] [{}]: {}", self.thread, self.level, self.logger, self.message)?;
+        if let Some(throwable) = &self.throwable {
+            write!(f, "\n{}", throwable.trim_end())?;
+        }
+        Ok(())
+    }
+}
+
+/// Result of feeding a line to the [`Log4jParser`]
+pub enum Parsed {
+    /// The line is not part of a log event
+    Line(String),
+    /// The line closed a log event
+    Record(LogRecord),
+    /// The line belongs to an event that is not closed yet
+    Pending,
+}
+
+/// Groups the lines of the game output into log4j events
+#[derive(Default)]
+pub struct Log4jParser {
+    event: Option<String>,
+}
+impl Log4jParser {
+    pub fn feed(&mut self, line: String) -> Parsed {
+        let mut event = match self.event.take() {
+            Some(event) => event,
+            None if line.trim_start().starts_with(EVENT_START) => String::new(),
+            None => return Parsed::Line(line),
+        };
+        event.push_str(&line);
+        event.push('\n');
+        if line.contains(EVENT_END) {
+            return match parse(&event) {
+                Some(record) => Parsed::Record(record),
+                None => Parsed::Line(event.trim_end().to_owned()),
+            };
+        }
+        if event.len() > MAX_EVENT_SIZE {
+            return Parsed::Line(event.trim_end().to_owned());
+        }
+        self.event = Some(event);
+        Parsed::Pending
+    }
+}
+
+/// Renders a line of the game, log events are colored by level
+pub fn render(line: &OutputLine) -> String {
+    let Some(record) = &line.record else {
+        return line.line.clone();
+    };
+    match record.level.as_str() {
+        "FATAL" | "ERROR" => style(&line.line).red().bold().to_string(),
+        "WARN" => style(&line.line).yellow().to_string(),
+        "DEBUG" | "TRACE" => style(&line.line).dim().to_string(),
+        _ => line.line.clone(),
+    }
+}
+
+fn parse(event: &str) -> Option<LogRecord> {
+    let start = event.find(EVENT_START)? + EVENT_START.len();
+    let attributes = &event[start..start + event[start..].find('>')?];
+    Some(LogRecord {
+        level: attribute(attributes, "level")?,
+        thread: attribute(attributes, "thread").unwrap_or_default(),
+        logger: attribute(attributes, "logger").unwrap_or_default(),
+        message: element(event, "log4j:Message").unwrap_or_default(),
+        throwable: element(event, "log4j:Throwable"),
+    })
+}
+/// Value of the attribute `name` in the attributes of a start tag
+fn attribute(attributes: &str, name: &str) -> Option<String> {
+    let pattern = format!(" {}=\"", name);
+    let start = attributes.find(&pattern)? + pattern.len();
+    let end = start + attributes[start..].find('"')?;
+    Some(unescape(&attributes[start..end]))
+}
+/// Text of the element `name`, unwrapping its CDATA section
+fn element(event: &str, name: &str) -> Option<String> {
+    let open = format!("<{}>", name);
+    let close = format!("</{}>", name);
+    let start = event.find(&open)? + open.len();
+    let end = start + event[start..].find(&close)?;
+    let content = &event[start..end];
+    match content.strip_prefix("<![CDATA[").and_then(|c| c.strip_suffix("]]>")) {
+        Some(cdata) => Some(cdata.to_owned()),
+        None => Some(unescape(content)),
+    }
+}
+fn unescape(text: &str) -> String {
+    text.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&apos;", "'")
+        .replace("&amp;", "&")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn feed(parser: &mut Log4jParser, lines: &[&str]) -> Vec<Parsed> {
+        lines.iter().map(|line| parser.feed(line.to_string())).collect()
+    }
+
+    #[test]
+    fn parses_an_event_across_lines() {
+        let mut parser = Log4jParser::default();
+        let parsed = feed(
+            &mut parser,
+            &[
+                r#"<log4j:Event logger="ezc" timestamp="1700000000000" level="WARN" thread="Render thread">"#,
+                r#"  <log4j:Message><![CDATA[Missing <sound> & more]]></log4j:Message>"#,
+                r#"  <log4j:Throwable>java.io.IOException: &quot;x&quot;</log4j:Throwable>"#,
+                "</log4j:Event>",
+            ],
+        );
+        assert!(matches!(parsed[..3], [Parsed::Pending, Parsed::Pending, Parsed::Pending]));
+        let Parsed::Record(record) = &parsed[3] else {
+            panic!("the event is not parsed");
+        };
+        assert_eq!(record.level, "WARN");
+        assert_eq!(record.thread, "Render thread");
+        assert_eq!(record.logger, "ezc");
+        assert_eq!(record.message, "Missing <sound> & more");
+        assert_eq!(record.throwable.as_deref(), Some("java.io.IOException: \"x\""));
+    }
+
+    #[test]
+    fn keeps_plain_lines_and_broken_events() {
+        let mut parser = Log4jParser::default();
+        let Parsed::Line(line) = parser.feed(String::from("Setting user: test")) else {
+            panic!("a plain line is not kept");
+        };
+        assert_eq!(line, "Setting user: test");
+        let parsed = feed(&mut parser, &[r#"<log4j:Event thread="main">"#, "</log4j:Event>"]);
+        let Parsed::Line(line) = &parsed[1] else {
+            panic!("an event without level is not kept as text");
+        };
+        assert_eq!(line, "<log4j:Event thread=\"main\">\n</log4j:Event>");
+    }
+}
@@ -0,0 +1,254 @@
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::{Duration, SystemTime};
+
+use log::{trace, warn};
+
+use crate::mconf;
+use crate::mlog4j::{Log4jParser, Parsed};
+use crate::mvers::{OutputLine, Stream};
+
+pub(crate) static LOGS_DIR: &str = "logs";
+static LOG_PREFIX: &str = "mcwr-";
+static LOG_EXTENSION: &str = ".log";
+/// Length of the timestamps of the names, `YYYY-MM-DDTHH-MM-SS.mmmZ`
+static TIMESTAMP_LEN: usize = 24;
+
+/// Log file of one game session
+pub struct SessionLog {
+    path: PathBuf,
+    file: BufWriter<File>,
+}
+impl SessionLog {
+    /// Creates the log file of a new session of `version`
+    pub fn create(version: &str) -> io::Result<SessionLog> {
+        let (path, file) = create(version)?;
+        Ok(SessionLog { path, file: BufWriter::new(file) })
+    }
+    /// Appends a line of the game to the log
+    pub fn write(&mut self, line: &OutputLine) {
+        let stream = match line.stream {
+            Stream::Stdout => "STDOUT",
+            Stream::Stderr => "STDERR",
+        };
+        let written = writeln!(
+            self.file,
+            "[{}] [{}] {}",
+            humantime::format_rfc3339_millis(line.time),
+            stream,
+            line.line
+        )
+        .and_then(|_| self.file.flush());
+        if let Err(e) = written {
+            warn!("CANNOT WRITE SESSION LOG {:?}: {}", self.path, e);
+        }
+    }
+}
+
+/// Creates the log file of a new session of `version`, rotating the old ones first.
+/// The raw file is used by detached games, which write their output directly to it
+pub fn create(version: &str) -> io::Result<(PathBuf, File)> {
+    let dir = dir(version);
+    fs::create_dir_all(&dir)?;
+    rotate(version)?;
+    let path = dir.join(file_name(version, SystemTime::now()));
+    trace!("CREATING SESSION LOG {:?}", path);
+    let file = File::create(&path)?;
+    Ok((path, file))
+}
+
+/// Directory holding the session logs of a version
+pub fn dir(version: &str) -> PathBuf {
+    Path::new(&mconf::config().versions)
+        .join(version)
+        .join(LOGS_DIR)
+}
+
+/// Session logs of a version, the most recent first
+pub fn sessions(version: &str) -> io::Result<Vec<PathBuf>> {
+    let dir = dir(version);
+    if !dir.exists() {
+        return Ok(Vec::new());
+    }
+    let prefix = format!("{}{}-", LOG_PREFIX, version);
+    let mut sessions: Vec<PathBuf> = fs::read_dir(dir)?
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .filter(|path| {
+            path.file_name()
+                .and_then(|name| name.to_str())
+                .is_some_and(|name| name.starts_with(&prefix) && name.ends_with(LOG_EXTENSION))
+        })
+        .collect();
+    // the names end with the timestamp, so sorting them sorts the sessions
+    sessions.sort();
+    sessions.reverse();
+    Ok(sessions)
+}
+
+/// Time span of a session log, from the timestamp in its name to its last write
+pub fn window(path: &Path) -> Option<(SystemTime, SystemTime)> {
+    let name = path.file_name()?.to_str()?.strip_suffix(LOG_EXTENSION)?;
+    let started = parse_timestamp(name.get(name.len().checked_sub(TIMESTAMP_LEN)?..)?)?;
+    let ended = path.metadata().ok()?.modified().ok()?;
+    Some((started, ended))
+}
+/// Name of the log of a session of `version` started at `time`, it ends with the timestamp
+fn file_name(version: &str, time: SystemTime) -> String {
+    format!("{}{}-{}{}", LOG_PREFIX, version, timestamp(time), LOG_EXTENSION)
+}
+/// The timestamp is used in file names, so the colons of RFC 3339 are replaced
+fn timestamp(time: SystemTime) -> String {
+    humantime::format_rfc3339_millis(time).to_string().replace(':', "-")
+}
+fn parse_timestamp(timestamp: &str) -> Option<SystemTime> {
+    let (date, time) = timestamp.split_once('T')?;
+    humantime::parse_rfc3339(&format!("{}T{}", date, time.replace('-', ":"))).ok()
+}
+/// Renames the session logs of `version` in `dir` after `to`, the name of the version renamed
+pub fn rename(dir: &Path, version: &str, to: &str) -> io::Result<()> {
+    if !dir.exists() {
+        return Ok(());
+    }
+    let prefix = format!("{}{}-", LOG_PREFIX, version);
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        let Some(rest) = path.file_name().and_then(|name| name.to_str()).and_then(|name| name.strip_prefix(&prefix)) else {
+            continue;
+        };
+        let renamed = dir.join(format!("{}{}-{}", LOG_PREFIX, to, rest));
+        trace!("RENAMING SESSION LOG {:?} TO {:?}", path, renamed);
+        fs::rename(&path, renamed)?;
+    }
+    Ok(())
+}
+
+/// Removes the oldest session logs of a version, keeping at most `logs_max_sessions` - 1 files
+/// and `logs_max_size` bytes so the new session fits in the limits
+fn rotate(version: &str) -> io::Result<()> {
+    let max_sessions: usize = mconf::config().logs_max_sessions;
+    let max_size: u64 = mconf::config().logs_max_size;
+    let mut total = 0;
+    for (i, session) in sessions(version)?.iter().enumerate() {
+        total += session.metadata()?.len();
+        if i + 1 >= max_sessions || total > max_size {
+            trace!("ROTATING SESSION LOG {:?}", session);
+            fs::remove_file(session)?;
+        }
+    }
+    Ok(())
+}
+
+/// Prints a session log, with `follow` keeps printing what is appended to it
+pub fn print(path: &Path, follow: bool) -> io::Result<()> {
+    let mut file = File::open(path)?;
+    let mut stdout = io::stdout();
+    let mut lines = Lines::default();
+    let mut content = Vec::new();
+    file.read_to_end(&mut content)?;
+    lines.push(&content, &mut stdout)?;
+    if !follow {
+        return lines.finish(&mut stdout);
+    }
+    loop {
+        thread::sleep(Duration::from_millis(500));
+        // a length smaller than the read position means the file was truncated
+        let position = file.stream_position()?;
+        if file.metadata()?.len() < position {
+            file.seek(SeekFrom::Start(0))?;
+        }
+        let mut appended = Vec::new();
+        file.read_to_end(&mut appended)?;
+        lines.push(&appended, &mut stdout)?;
+        stdout.flush()?;
+    }
+}
+
+/// Lines of a session log being printed. The detached games write their raw output to the log,
+/// its log4j events are rendered like the lines written by [`SessionLog`]
+#[derive(Default)]
+struct Lines {
+    /// Start of a line that is not complete yet
+    partial: Vec<u8>,
+    parser: Log4jParser,
+}
+impl Lines {
+    fn push(&mut self, bytes: &[u8], out: &mut impl Write) -> io::Result<()> {
+        self.partial.extend_from_slice(bytes);
+        while let Some(end) = self.partial.iter().position(|byte| *byte == b'\n') {
+            let line: Vec<u8> = self.partial.drain(..=end).collect();
+            self.line(&line, out)?;
+        }
+        Ok(())
+    }
+    /// Prints the last line, when the log does not end with a new line
+    fn finish(mut self, out: &mut impl Write) -> io::Result<()> {
+        let line = std::mem::take(&mut self.partial);
+        if line.is_empty() {
+            return Ok(());
+        }
+        self.line(&line, out)
+    }
+    fn line(&mut self, line: &[u8], out: &mut impl Write) -> io::Result<()> {
+        let line = String::from_utf8_lossy(line).trim_end_matches(['\n', '\r']).to_owned();
+        if is_written(&line) {
+            return writeln!(out, "{}", line);
+        }
+        match self.parser.feed(line) {
+            Parsed::Line(line) => writeln!(out, "{}", line),
+            Parsed::Record(record) => writeln!(out, "{}", record),
+            Parsed::Pending => Ok(()),
+        }
+    }
+}
+/// Whether `line` was written by [`SessionLog`], `[time] [STREAM] line`
+fn is_written(line: &str) -> bool {
+    let Some((time, rest)) = line.strip_prefix('[').and_then(|line| line.split_once("] [")) else {
+        return false;
+    };
+    (rest.starts_with("STDOUT] ") || rest.starts_with("STDERR] ")) && humantime::parse_rfc3339(time).is_ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn window_starts_at_the_timestamp_of_the_name() {
+        let dir = std::env::temp_dir().join(format!("mcwr-mlogs-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let started = SystemTime::UNIX_EPOCH + Duration::from_millis(1_792_395_410_123);
+        let name = file_name("fabric-1.20.4", started);
+        assert_eq!(timestamp(started).len(), TIMESTAMP_LEN);
+        let path = dir.join(&name);
+        File::create(&path).unwrap();
+        let (start, end) = window(&path).unwrap();
+        assert_eq!(start, started);
+        assert!(end >= start);
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn raw_output_reads_like_written_lines() {
+        let log = concat!(
+            "[2026-10-19T08:00:00.000Z] [STDOUT] [main/INFO] [b]: written\n",
+            "<log4j:Event logger=\"a\" timestamp=\"1\" level=\"WARN\" thread=\"main\">\n",
+            "  <log4j:Message><![CDATA[raw]]></log4j:Message>\n",
+            "</log4j:Event>\n",
+            "plain",
+        );
+        let mut out = Vec::new();
+        let mut lines = Lines::default();
+        // the log is read in pieces while it is followed
+        for piece in log.as_bytes().chunks(7) {
+            lines.push(piece, &mut out).unwrap();
+        }
+        lines.finish(&mut out).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "[2026-10-19T08:00:00.000Z] [STDOUT] [main/INFO] [b]: written\n[main/WARN] [a]: raw\nplain\n"
+        );
+    }
+}
@@ -0,0 +1,201 @@
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use log::{trace, warn};
+use serde::{Deserialize, Serialize};
+
+use crate::mconf;
+use crate::mvers::{self, Version};
+
+pub(crate) static RUNNING_DIR: &str = ".running";
+
+/// State of a running game, written under the version home while it runs
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RunState {
+    pub pid: u32,
+    pub version: String,
+    pub account: String,
+    /// Seconds since the unix epoch
+    pub started: u64,
+    /// Start time of the process in clock ticks since boot, tells the game apart from another
+    /// process that reuses its pid
+    #[serde(default)]
+    pub start_time: Option<u64>,
+    pub log: Option<String>,
+}
+impl RunState {
+    /// Whether the process of the game is still running, and is still the game
+    pub fn running(&self) -> bool {
+        alive(self.pid) && (self.start_time.is_none() || start_time(self.pid) == self.start_time)
+    }
+    /// Time since the game was started
+    pub fn uptime(&self) -> Duration {
+        Duration::from_secs(now().saturating_sub(self.started))
+    }
+    /// Resident memory of the game in bytes, read from /proc
+    pub fn memory(&self) -> Option<u64> {
+        let status = fs::read_to_string(format!("/proc/{}/status", self.pid)).ok()?;
+        let rss = status.lines().find(|line| line.starts_with("VmRSS:"))?;
+        let kb: u64 = rss.split_whitespace().nth(1)?.parse().ok()?;
+        Some(kb * 1024)
+    }
+}
+
+/// Registers a running game of `version`
+pub fn register(version: &Version, pid: u32, log: Option<&Path>) -> io::Result<()> {
+    let state = RunState {
+        pid,
+        version: version.version.clone(),
+        account: version.account(),
+        started: now(),
+        start_time: start_time(pid),
+        log: log.map(|log| log.to_string_lossy().into_owned()),
+    };
+    let dir = dir(&version.version);
+    fs::create_dir_all(&dir)?;
+    trace!("REGISTERING RUNNING GAME {:?}", state);
+    let content = toml::to_string(&state).map_err(io::Error::other)?;
+    fs::write(dir.join(pid.to_string()), content)
+}
+/// Removes the state of a game that is no longer running
+pub fn unregister(version: &str, pid: u32) {
+    let path = dir(version).join(pid.to_string());
+    trace!("UNREGISTERING RUNNING GAME {:?}", path);
+    fs::remove_file(path).ok();
+}
+
+/// Games currently running, the states of the games that died are cleaned up
+pub fn list() -> io::Result<Vec<RunState>> {
+    let mut states = Vec::new();
+    for home in fs::read_dir(&mconf::config().versions)? {
+        let running = home?.path().join(RUNNING_DIR);
+        let Ok(entries) = fs::read_dir(&running) else {
+            continue;
+        };
+        for entry in entries {
+            let path = entry?.path();
+            let state = fs::read_to_string(&path)
+                .ok()
+                .and_then(|content| toml::from_str::<RunState>(&content).ok());
+            match state {
+                Some(state) if state.running() => states.push(state),
+                state => {
+                    trace!("REMOVING STALE STATE {:?}", path);
+                    fs::remove_file(&path).ok();
+                    // the last write of its log is the best guess of when a detached game ended
+                    if let Some(state) = state {
+                        let ended = state.log.as_ref().and_then(|log| fs::metadata(log).and_then(|meta| meta.modified()).ok());
+                        if let Some(played) = ended.and_then(|ended| ended.duration_since(UNIX_EPOCH).ok()) {
+                            let started = UNIX_EPOCH + Duration::from_secs(state.started);
+                            mvers::record_session(&state.version, started, Duration::from_secs(played.as_secs().saturating_sub(state.started)));
+                        }
+                    }
+                }
+            }
+        }
+    }
+    states.sort_by_key(|state| state.started);
+    Ok(states)
+}
+
+/// Stops a game, sending SIGTERM and SIGKILL if it is still alive after `grace`.
+/// Returns whether the game had to be killed, nothing is sent if its pid is not the game anymore
+pub fn stop(state: &RunState, grace: Duration) -> io::Result<bool> {
+    let pid = state.pid;
+    if !state.running() {
+        trace!("GAME {} IS NOT RUNNING", pid);
+        return Ok(false);
+    }
+    trace!("SENDING SIGTERM TO {}", pid);
+    signal(pid, Signal::Term)?;
+    let start = Instant::now();
+    while start.elapsed() < grace {
+        if !state.running() {
+            return Ok(false);
+        }
+        thread::sleep(Duration::from_millis(200));
+    }
+    warn!("GAME {} STILL ALIVE AFTER {:?}, SENDING SIGKILL", pid, grace);
+    signal(pid, Signal::Kill)?;
+    Ok(true)
+}
+
+enum Signal {
+    Term,
+    Kill,
+}
+#[cfg(unix)]
+fn signal(pid: u32, signal: Signal) -> io::Result<()> {
+    let signal = match signal {
+        Signal::Term => libc::SIGTERM,
+        Signal::Kill => libc::SIGKILL,
+    };
+    // SAFETY: kill has no memory safety requirements
+    if unsafe { libc::kill(pid as libc::pid_t, signal) } == 0 {
+        Ok(())
+    } else {
+        Err(io::Error::last_os_error())
+    }
+}
+#[cfg(not(unix))]
+fn signal(_: u32, _: Signal) -> io::Result<()> {
+    Err(io::Error::new(io::ErrorKind::Unsupported, "signals are only supported on unix"))
+}
+#[cfg(unix)]
+fn alive(pid: u32) -> bool {
+    // a zombie keeps its pid until it is reaped, it is not running anymore
+    let status = fs::read_to_string(format!("/proc/{}/status", pid));
+    if let Ok(status) = status {
+        return !status.lines().any(|line| line.starts_with("State:") && line.contains("zombie"));
+    }
+    // SAFETY: signal 0 only checks that the process exists
+    unsafe { libc::kill(pid as libc::pid_t, 0) == 0 }
+}
+#[cfg(not(unix))]
+fn alive(_: u32) -> bool {
+    false
+}
+/// Field 22 of `/proc/<pid>/stat`, the start time of the process
+fn start_time(pid: u32) -> Option<u64> {
+    let stat = fs::read_to_string(format!("/proc/{}/stat", pid)).ok()?;
+    // the name of the process, in parentheses, may contain spaces
+    let fields = &stat[stat.rfind(')')? + 1..];
+    fields.split_whitespace().nth(19)?.parse().ok()
+}
+
+fn dir(version: &str) -> PathBuf {
+    Path::new(&mconf::config().versions)
+        .join(version)
+        .join(RUNNING_DIR)
+}
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|time| time.as_secs())
+        .unwrap_or_default()
+}
+
+#[cfg(all(test, target_os = "linux"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reused_pid_is_not_running() {
+        let pid = std::process::id();
+        let mut state = RunState {
+            pid,
+            version: String::from("test"),
+            account: String::from("test"),
+            started: now(),
+            start_time: start_time(pid),
+            log: None,
+        };
+        assert!(state.start_time.is_some());
+        assert!(state.running());
+        state.start_time = state.start_time.map(|time| time + 1);
+        assert!(!state.running());
+    }
+}
@@ -0,0 +1,83 @@
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex};
+
+use indicatif::{MultiProgress, ProgressBar, ProgressStyle};
+
+use crate::mvers::{Category, DownloadCallback, DownloadEvent};
+
+static TOTAL_TEMPLATE: &str =
+    "{prefix:>10} [{bar:40.green/red}] {bytes:>10}/{total_bytes:<10} {binary_bytes_per_sec:>12} ETA {eta}";
+static CATEGORY_TEMPLATE: &str =
+    "{prefix:>10} [{bar:40.cyan/blue}] {bytes:>10}/{total_bytes:<10} {binary_bytes_per_sec:>12} ETA {eta}";
+
+/// Bars of a running download, the overall one and one per category
+struct Bars {
+    // keeps the bars drawn
+    _multi: MultiProgress,
+    total: ProgressBar,
+    categories: BTreeMap<Category, ProgressBar>,
+}
+impl Bars {
+    fn new(version: &str, size: u64, categories: &BTreeMap<Category, u64>) -> Self {
+        let multi = MultiProgress::new();
+        let total = multi.add(bar(TOTAL_TEMPLATE, version, size));
+        let categories = categories
+            .iter()
+            .map(|(category, size)| (*category, multi.add(bar(CATEGORY_TEMPLATE, name(*category), *size))))
+            .collect();
+        Bars { _multi: multi, total, categories }
+    }
+    fn file(&self, category: Category, size: u64) {
+        self.total.inc(size);
+        if let Some(bar) = self.categories.get(&category) {
+            bar.inc(size);
+        }
+    }
+    /// The files already in the CAS are not reported, the bars are filled when it ends
+    fn finish(&self) {
+        self.categories.values().for_each(ProgressBar::finish);
+        self.total.finish();
+    }
+}
+
+/// Draws the progress of a download in the terminal, nothing is drawn if stderr is not a terminal
+pub fn bars() -> DownloadCallback {
+    let bars: Mutex<Option<Bars>> = Mutex::new(None);
+    Arc::new(move |event| {
+        let Ok(mut bars) = bars.lock() else { return };
+        match event {
+            DownloadEvent::Start { versions, size, categories, .. } => {
+                *bars = Some(Bars::new(&versions.join(", "), size, &categories));
+            }
+            DownloadEvent::File { category, size, .. } => {
+                if let Some(bars) = bars.as_ref() {
+                    bars.file(category, size);
+                }
+            }
+            // the bars end with the first version installed, all the files are downloaded then
+            DownloadEvent::Done { .. } | DownloadEvent::Failed { .. } => {
+                if let Some(bars) = bars.take() {
+                    bars.finish();
+                }
+            }
+        }
+    })
+}
+/// Style that draws nothing, for the bars of each file of dwldutil
+pub fn hidden() -> ProgressStyle {
+    ProgressStyle::with_template("").unwrap_or_else(|_| ProgressStyle::default_bar())
+}
+
+fn bar(template: &str, prefix: &str, size: u64) -> ProgressBar {
+    let style = ProgressStyle::with_template(template).unwrap_or_else(|_| ProgressStyle::default_bar());
+    ProgressBar::new(size).with_style(style.progress_chars("##-")).with_prefix(prefix.to_owned())
+}
+fn name(category: Category) -> &'static str {
+    match category {
+        Category::Client => "client",
+        Category::Libraries => "libraries",
+        Category::Natives => "natives",
+        Category::Assets => "assets",
+        Category::Java => "java",
+    }
+}
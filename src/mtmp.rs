@@ -6,8 +6,8 @@ macro_rules! temp {
         log::trace!("TMP DIR: {:?}", tempdir);
         tempdir.push($file);
         if tempdir.exists() {
-            std::fs::remove_file(&tempdir).unwrap();
+            std::fs::remove_file(&tempdir).ok();
         }
-        tempdir.clone().to_str().unwrap()
+        tempdir.to_string_lossy().into_owned()
     }};
 }
@@ -1,21 +1,35 @@
-use dwldutil::Downloader;
+use dwldutil::{DLFile, DLHashes, Downloader};
 use log::{info, trace, warn};
-use mcd::api::client::Client;
+use mcd::api::assets::Assets;
+use mcd::api::client::{ArgumentValue, Client, ValueField};
 use mcd::api::manifest::{Latest, Manifest};
 use mcd::api::{ApiClientError, ApiClientUtil};
 use mcd::command::{build_args, Command};
-use mcd::errors::CommandError;
+use mcd::errors::{CommandError, FetchError};
 use mcd::file::fetch_client;
 use mcd::java::JavaUtil;
 use mcd::libs::LibsUtil;
 use mcd::resource::ResourceUtil;
 use serde::{Deserialize, Serialize};
 
-use crate::errors::{self, DownloadError, ReadingError};
-use crate::mconf;
-use std::collections::HashMap;
-use std::io::{BufRead, BufReader};
+use crate::errors::{self, DownloadError, McwrError, ReadingError};
+use crate::mconf::{self, VersionConfig};
+use crate::{mdirs, minherit, mprogress};
+use crate::mfeat::Features;
+use crate::mjava::{self, Damage};
+use crate::mlog4j::{Log4jParser, LogRecord, Parsed};
+use crate::mlogs::{self, SessionLog};
+use crate::mproc;
+use std::cell::Cell;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::io::{self, BufRead, BufReader, Read};
+use std::panic::{self, AssertUnwindSafe, UnwindSafe};
 use std::path::{Path, PathBuf};
+use std::process::{self, Child, ExitStatus, Stdio};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::{Arc, Mutex, Once};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 use std::fs;
 
 #[cfg(target_os = "linux")]
@@ -24,10 +38,156 @@ static CP_SEPARATOR: char = ':';
 static CP_SEPARATOR: char = ';';
 
 #[cfg(target_os = "linux")]
-static JAVA_BIN: &str = "java";
+pub(crate) static JAVA_BIN: &str = "java";
 #[cfg(target_os = "windows")]
-static JAVA_BIN: &str = "java.exe";
+pub(crate) static JAVA_BIN: &str = "java.exe";
 static META_FILE: &str = ".info";
+thread_local! {
+    /// Set while [`unpanic`] runs, its panics are not printed
+    static UNPANIC: Cell<bool> = const { Cell::new(false) };
+}
+
+/// Stream of the game process a line was read from
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Stream {
+    Stdout,
+    Stderr,
+}
+
+/// A line written by the game, stamped with the moment it was read.
+/// Log4j events are parsed into `record` and rendered as plain text in `line`
+#[derive(Debug, Clone)]
+pub struct OutputLine {
+    pub stream: Stream,
+    pub time: SystemTime,
+    pub line: String,
+    pub record: Option<LogRecord>,
+}
+
+/// Receives the output lines of a running version
+pub type OutputCallback<'a> = Box<dyn FnMut(OutputLine) + 'a>;
+
+/// Kind of the files of a download
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Category {
+    /// The client jar and its logging configuration
+    Client,
+    Libraries,
+    Natives,
+    Assets,
+    Java,
+}
+/// Progress of [`download`]
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum DownloadEvent {
+    /// The files of the versions are resolved, `size` is the sum of the known sizes and
+    /// `categories` the known size of each category with files
+    Start { versions: Vec<String>, files: usize, size: u64, categories: BTreeMap<Category, u64> },
+    /// A file was downloaded and verified
+    File { path: String, category: Category, size: u64 },
+    /// The version is installed in `home`
+    Done { version: String, home: String },
+    /// The version cannot be installed, only sent by [`download_all`]
+    Failed { version: String, error: String },
+}
+/// Receives the progress of a download, the files are reported from the download tasks
+pub type DownloadCallback = Arc<dyn Fn(DownloadEvent) + Send + Sync>;
+
+/// A version of the manifest
+#[derive(Debug, Clone, Serialize)]
+pub struct ManifestEntry {
+    pub id: String,
+    #[serde(rename = "type")]
+    pub version_type: String,
+    pub url: String,
+    pub time: String,
+    pub release_time: String,
+}
+
+/// Options of a launch, given per run or saved as the defaults of a version
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LaunchOptions {
+    /// Maximum heap (`4G`), or `MIN:MAX` to set the initial heap as well
+    pub memory: Option<String>,
+    /// Name of a JVM flags preset of the `presets` configuration
+    pub preset: Option<String>,
+    /// Java home or binary used instead of the runtime of the version, or `auto`
+    pub java: Option<String>,
+    pub jvm_args: Vec<String>,
+    pub game_args: Vec<String>,
+    pub features: Features,
+}
+impl LaunchOptions {
+    /// Options of a run over these defaults, the extra arguments of both are kept
+    pub fn with(&self, run: &LaunchOptions) -> LaunchOptions {
+        LaunchOptions {
+            memory: run.memory.clone().or_else(|| self.memory.clone()),
+            preset: run.preset.clone().or_else(|| self.preset.clone()),
+            java: run.java.clone().or_else(|| self.java.clone()),
+            jvm_args: [self.jvm_args.as_slice(), run.jvm_args.as_slice()].concat(),
+            game_args: [self.game_args.as_slice(), run.game_args.as_slice()].concat(),
+            features: self.features.with(&run.features),
+        }
+    }
+    /// Replaces these defaults with the options given in `run`
+    pub fn update(&mut self, run: &LaunchOptions) {
+        if run.memory.is_some() {
+            self.memory = run.memory.clone();
+        }
+        if run.preset.is_some() {
+            self.preset = run.preset.clone();
+        }
+        if run.java.is_some() {
+            self.java = run.java.clone();
+        }
+        if !run.jvm_args.is_empty() {
+            self.jvm_args = run.jvm_args.clone();
+        }
+        if !run.game_args.is_empty() {
+            self.game_args = run.game_args.clone();
+        }
+        self.features.update(&run.features);
+    }
+    /// The launch options set in the `mcwr.toml` of a version
+    fn from_overrides(overrides: &VersionConfig) -> LaunchOptions {
+        LaunchOptions {
+            memory: overrides.memory.clone(),
+            preset: overrides.preset.clone(),
+            java: overrides.java.clone(),
+            jvm_args: overrides.jvm_args.clone(),
+            ..LaunchOptions::default()
+        }
+    }
+    /// JVM flags of the preset, memory and extra arguments
+    fn jvm_flags(&self) -> Vec<String> {
+        let mut flags = Vec::new();
+        let preset = self
+            .preset
+            .clone()
+            .or_else(|| mconf::config().preset.clone())
+            .filter(|preset| !preset.is_empty());
+        if let Some(preset) = preset {
+            match mconf::config().presets.get(&preset) {
+                Some(preset) => flags.extend(preset.iter().cloned()),
+                None => warn!("JVM PRESET {} NOT FOUND", preset),
+            }
+        }
+        if let Some(memory) = &self.memory {
+            match memory.split_once(':') {
+                Some((min, max)) => {
+                    flags.push(format!("-Xms{}", min));
+                    flags.push(format!("-Xmx{}", max));
+                }
+                None => flags.push(format!("-Xmx{}", memory)),
+            }
+        }
+        flags.extend(self.jvm_args.iter().cloned());
+        flags
+    }
+}
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Version {
@@ -44,138 +204,661 @@ pub struct Version {
     pub libraries: String,
     pub classpath: String,
     pub java_version: usize,
+    /// Start of the last session, seconds since the unix epoch
+    #[serde(default)]
+    pub last_played: Option<u64>,
+    /// Seconds played in the recorded sessions
+    #[serde(default)]
+    pub playtime: u64,
+    /// Launch options used by default with this version
+    #[serde(default)]
+    pub launch: LaunchOptions,
 }
 impl Version {
-    pub fn run(self, stdout_callback: fn(String), stderr_callback: fn(String)) -> Result<(), CommandError> {
-        trace!("RUNNING VERSION {}", self.version);
+    /// Starts the version and returns a handle to the running game.
+    /// stdout and stderr are pumped concurrently from the moment the game starts
+    pub fn launch(&self, options: &LaunchOptions) -> Result<LaunchHandle<'_>, McwrError> {
+        trace!("LAUNCHING VERSION {}", self.version);
+        let mut command = self.command(options)?;
+        trace!("COMMAND BUILDED... EXECUTING");
+        let mut child = command
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .map_err(CommandError::from)?;
+        trace!("VERSION {} STARTED WITH PID {}", self.version, child.id());
+
+        let (sender, receiver) = mpsc::channel();
+        let mut pumps = Vec::new();
+        if let Some(stdout) = child.stdout.take() {
+            pumps.push(pump(stdout, Stream::Stdout, sender.clone()));
+        }
+        if let Some(stderr) = child.stderr.take() {
+            pumps.push(pump(stderr, Stream::Stderr, sender.clone()));
+        }
+        let mut handle = LaunchHandle {
+            version: self.version.clone(),
+            started: SystemTime::now(),
+            pid: child.id(),
+            child: Arc::new(Mutex::new(child)),
+            receiver,
+            pumps,
+            subscribers: Vec::new(),
+        };
+        // every session is written to its own log file
+        match SessionLog::create(&self.version) {
+            Ok(mut log) => handle.on_output(Box::new(move |line| log.write(&line))),
+            Err(e) => warn!("CANNOT CREATE SESSION LOG: {}", e),
+        }
+        if let Err(e) = mproc::register(self, handle.pid(), None) {
+            warn!("CANNOT REGISTER RUNNING GAME: {}", e);
+        }
+        record_session(&self.version, handle.started, Duration::ZERO);
+        Ok(handle)
+    }
+    /// Starts the version in the background and returns its pid without waiting for it.
+    /// The output of the game goes straight to a new session log
+    pub fn launch_detached(&self, options: &LaunchOptions) -> Result<u32, McwrError> {
+        trace!("LAUNCHING DETACHED VERSION {}", self.version);
+        let mut command = self.command(options)?;
+        let (log, file) = mlogs::create(&self.version).map_err(CommandError::from)?;
+        command
+            .stdin(Stdio::null())
+            .stdout(file.try_clone().map_err(CommandError::from)?)
+            .stderr(file);
+        // own process group, so the game survives the signals sent to the terminal
+        #[cfg(unix)]
+        std::os::unix::process::CommandExt::process_group(&mut command, 0);
+        let child = command.spawn().map_err(CommandError::from)?;
+        trace!("VERSION {} DETACHED WITH PID {}", self.version, child.id());
+        if let Err(e) = mproc::register(self, child.id(), Some(&log)) {
+            warn!("CANNOT REGISTER RUNNING GAME: {}", e);
+        }
+        // the playtime of a detached game is recorded once it is found dead, see `mproc::list`
+        record_session(&self.version, SystemTime::now(), Duration::ZERO);
+        Ok(child.id())
+    }
+    /// Name of the player the version is launched with
+    pub fn account(&self) -> String {
+        self.data
+            .get("auth_player_name")
+            .cloned()
+            .or_else(|| self.overrides().data.remove("auth_player_name"))
+            .or_else(|| data().remove("auth_player_name"))
+            .unwrap_or_default()
+    }
+    /// Builds the java process of this version with `options` over the defaults of the version,
+    /// filling the placeholders of the launch arguments
+    pub fn command(&self, options: &LaunchOptions) -> Result<process::Command, McwrError> {
+        let overrides = self.overrides();
+        let options = LaunchOptions::from_overrides(&overrides)
+            .with(&self.launch)
+            .with(options);
         let mut data = data();
         trace!("WITH DEFAULT DATA {:?}", data);
-        data.insert("natives_directory".to_owned(), self.natives);
-        data.insert(
-            "classpath".to_owned(),
-            self.classpath,
-        );
-        data.insert("main_class".to_owned(), self.main);
-        data.insert("version_name".to_owned(), self.version);
-        data.insert("game_directory".to_owned(), self.pwd);
+        data.insert("natives_directory".to_owned(), self.natives.clone());
+        data.insert("classpath".to_owned(), self.classpath.clone());
+        data.insert("main_class".to_owned(), self.main.clone());
+        data.insert("version_name".to_owned(), self.version.clone());
+        data.insert("game_directory".to_owned(), self.game_dir().to_string_lossy().into_owned());
         data.insert("assets_index_name".to_owned(), self.assets.clone());
-        data.insert("version_type".to_owned(), self.version_type);
-        data.insert("library_directory".to_owned(), self.libraries);
-        for (k,v) in self.data {
-            data.insert(k, v);
+        data.insert("version_type".to_owned(), self.version_type.clone());
+        data.insert("library_directory".to_owned(), self.libraries.clone());
+        data.extend(overrides.data.clone());
+        for (k, v) in &self.data {
+            data.insert(k.clone(), v.clone());
         }
+        data.extend(options.features.data());
         trace!("FINAL DATA {:?}", data);
-        trace!("BUILDING COMMAND WITH ARGS \n\tJVM ARGS: {:?}\n\tGAME ARGS: {:?}", self.jvm_args, self.game_args);
-        let command = Command::from_args(self.game_args, self.jvm_args, data);
-        trace!("COMMAND BUILDED... EXECUTING");
-        let mut child = command.execute(self.java, vec![], self.java_version)?;
-
-        let stdout = child.stdout.take().expect("NO STDOUT");
-        let stderr = child.stderr.take().expect("NO STDERR");
-        let stdout = BufReader::new(stdout);
-        let stderr = BufReader::new(stderr);
-
-        for line in stdout.lines() {
-            let line = line.unwrap();
-            stdout_callback(line);
+        // the extra JVM flags go right before the main class so they override the version ones
+        let mut jvm = self.jvm_args.clone();
+        let main = jvm.iter().position(|arg| arg == "${main_class}").unwrap_or(jvm.len());
+        jvm.splice(main..main, options.jvm_flags());
+        let game = [self.game_args(&options.features, &overrides.options)?, options.game_args.clone()].concat();
+        trace!("BUILDING COMMAND WITH ARGS \n\tJVM ARGS: {:?}\n\tGAME ARGS: {:?}", jvm, game);
+        let filler = Command::from_args(game, jvm, data);
+        // mcd quotes long arguments for its argument files, they are passed directly here
+        let args = filler
+            .build_jvm_args()
+            .into_iter()
+            .chain(filler.build_game_args())
+            .map(|arg| arg.trim_matches('"').to_owned());
+        let mut command = process::Command::new(self.java_bin(options.java.as_deref())?);
+        command.args(args).current_dir(&mdirs::get().data);
+        Ok(command)
+    }
+    /// Java the version runs with, its own runtime unless another one is chosen
+    fn java_bin(&self, java: Option<&str>) -> Result<PathBuf, McwrError> {
+        match java {
+            Some(java) => mjava::select(java, self.java_version),
+            None => Ok(mdirs::get().data.join(&self.java)),
         }
-        for line in stderr.lines() {
-            let line = line.unwrap();
-            stderr_callback(line);
+    }
+    /// Directory the version is installed in
+    pub fn home(&self) -> PathBuf {
+        Path::new(&mconf::config().versions).join(&self.version)
+    }
+    /// Game directory, the relative paths of the metadata are relative to the data directory
+    pub fn game_dir(&self) -> PathBuf {
+        let pwd = self.overrides().pwd.unwrap_or_else(|| self.pwd.clone());
+        mdirs::get().data.join(pwd)
+    }
+    /// Mod loader of the version, guessed from its main class
+    pub fn loader(&self) -> &'static str {
+        let main = self.main.as_str();
+        if main.starts_with("net.fabricmc.") {
+            "fabric"
+        } else if main.starts_with("org.quiltmc.") {
+            "quilt"
+        } else if main.starts_with("cpw.mods.") || main.starts_with("net.minecraftforge.") || main == "net.minecraft.launchwrapper.Launch" {
+            if self.classpath.contains("neoforged") { "neoforge" } else { "forge" }
+        } else {
+            "vanilla"
         }
-        Ok(())
     }
-    pub fn from_path(dir: &PathBuf) -> Result<Version, ReadingError> {
+    /// Overrides of the configuration for this version, a broken `mcwr.toml` is ignored
+    fn overrides(&self) -> VersionConfig {
+        mconf::version_config(&self.version).unwrap_or_else(|e| {
+            warn!("IGNORING THE OVERRIDES OF {}: {}", self.version, e);
+            VersionConfig::default()
+        })
+    }
+    pub fn from_path(dir: &Path) -> Result<Version, ReadingError> {
         // define variables
         trace!("READING VERSION FROM PATH {:?}", dir);
-        let meta_file = format!("{}/{}", dir.to_str().expect("CANNOT CONVERT TO STRING"), META_FILE);
-        trace!("METADATA FILE WILL LOCATED IN {}", meta_file);
-        let meta_file = Path::new(meta_file.as_str());
+        let meta_file = dir.join(META_FILE);
+        trace!("METADATA FILE WILL LOCATED IN {:?}", meta_file);
         if !meta_file.exists() {
             warn!("PATH {:?} IS NOT A VERSION", dir);
         }
-        let content = fs::read_to_string(meta_file)?;
+        let content = fs::read_to_string(&meta_file).map_err(|e| ReadingError::ReadError(meta_file.clone(), e))?;
         trace!("CONTENT OF THE META FILE {}",content);
-        let version: Version = toml::from_str(&content)?;
+        let version: Version = toml::from_str(&content).map_err(|e| ReadingError::DeserializeError(meta_file, e))?;
         trace!("VERSION SUCCESSFUL LOADED");
         Ok(version)
     }
+    /// Game arguments of the version with `features` and the feature flags of its overrides
+    /// enabled. The rule-gated arguments are evaluated again from the client JSON, versions
+    /// without them get the legacy arguments
+    fn game_args(&self, features: &Features, overrides: &HashMap<String, bool>) -> Result<Vec<String>, McwrError> {
+        if features.is_empty() && overrides.is_empty() {
+            return Ok(self.game_args.clone());
+        }
+        let client = self.client().filter(|client| client.arguments.is_some());
+        let mut args = match &client {
+            Some(client) => {
+                let mut options = mconf::config().options.clone();
+                options.extend(overrides.clone());
+                options.extend(features.options());
+                trace!("EVALUATING GAME ARGS WITH OPTIONS {:?}", options);
+                build_args(client, options).0
+            }
+            None => [self.game_args.clone(), features.legacy_args()].concat(),
+        };
+        // the client JSONs before 1.20 have rule-gated arguments, but not the ones of quick play
+        if let Some(quick_play) = &features.quick_play {
+            let declared = client
+                .as_ref()
+                .and_then(|client| client.arguments.as_ref())
+                .is_some_and(|arguments| declares(&arguments.game, quick_play.placeholder()));
+            if !declared {
+                let legacy = features.legacy_quick_play().ok_or_else(|| McwrError::QuickPlayUnsupported {
+                    version: self.version.clone(),
+                    target: quick_play.target(),
+                })?;
+                args.extend(legacy);
+            }
+        }
+        args.extend(features.args());
+        Ok(args)
+    }
+    /// Client JSON of the version, kept in its home since it was downloaded
+    pub fn client(&self) -> Option<Client> {
+        let path = format!("{}/{}/{}.json", mconf::config().versions, self.version, self.version);
+        let client = fs::read_to_string(&path)
+            .map_err(|e| e.to_string())
+            .and_then(|json| serde_json::from_str::<Client>(&json).map_err(|e| e.to_string()));
+        match client {
+            Ok(client) if client.inherits_from.is_none() => Some(client),
+            Ok(_) => {
+                warn!("CLIENT JSON {} INHERITS FROM ANOTHER VERSION", path);
+                None
+            }
+            Err(e) => {
+                warn!("CANNOT READ CLIENT JSON {}: {}", path, e);
+                None
+            }
+        }
+    }
+    /// Checks the files of the version against the hashes of its client JSON. The assets are
+    /// checked when their index was downloaded, the runtime when it was downloaded by mcwr
+    pub fn verify(&self) -> Result<Vec<Damage>, McwrError> {
+        let client = self.client().ok_or_else(|| McwrError::NoClient(self.version.clone()))?;
+        let home = format!("{}/{}", mconf::config().versions, self.version);
+        let mut files = Vec::new();
+        match fetch_client(&client, &format!("{}/{}.jar", home, self.version)) {
+            Ok(file) => files.push(file),
+            Err(e) => warn!("WARN --- {}", e),
+        }
+        match LibsUtil::new().fetch(&self.libraries, &self.natives, &client) {
+            Ok((libs, _)) => files.extend(libs),
+            Err(e) => warn!("WARN --- {}", e),
+        }
+        let resources = &mconf::config().resources;
+        if let Some(logging) = client.logging.as_ref().map(|logging| &logging.client).filter(|logging| !logging.file.url.is_empty()) {
+            let path = format!("{}/log_configs/{}", resources, logging.file.id);
+            files.push(DLFile::new().with_path(&path).with_hashes(DLHashes::new().sha1(&logging.file.sha1)));
+        }
+        let index = format!("{}/indexes/{}.json", resources, self.assets);
+        if let Ok(index) = fs::read_to_string(&index) {
+            trace!("VERIFYING ASSETS OF {}", index);
+            let assets: Assets = serde_json::from_str(&index).map_err(io::Error::from)?;
+            for object in assets.objects.values() {
+                let path = format!("{}/objects/{}/{}", resources, &object.hash[..2], object.hash);
+                files.push(DLFile::new().with_path(&path).with_hashes(DLHashes::new().sha1(&object.hash)));
+            }
+        }
+        let mut damages = Vec::new();
+        let mut seen = std::collections::HashSet::new();
+        for file in files.iter().filter(|file| seen.insert(file.path.clone())) {
+            // the libraries are relative to the data directory, where the game runs
+            let path = mdirs::get().data.join(&file.path).to_string_lossy().into_owned();
+            trace!("VERIFYING {}", path);
+            let data = match fs::read(&path) {
+                Ok(data) => data,
+                Err(e) if e.kind() == io::ErrorKind::NotFound => {
+                    damages.push(Damage::Missing(path));
+                    continue;
+                }
+                Err(e) => return Err(e.into()),
+            };
+            let modified = file
+                .hashes
+                .hashes
+                .first()
+                .is_some_and(|(kind, hash)| !kind.verify_data(&data, hash));
+            if modified {
+                damages.push(Damage::Modified(path));
+            }
+        }
+        let runtime = mjava::runtimes()?
+            .into_iter()
+            .find(|runtime| !runtime.used_by([self]).is_empty());
+        if let Some(runtime) = runtime {
+            match mjava::verify(&runtime) {
+                Ok(runtime_damages) => damages.extend(runtime_damages.into_iter().map(|damage| match damage {
+                    Damage::Missing(path) => Damage::Missing(runtime.home.join(path).to_string_lossy().into_owned()),
+                    Damage::Modified(path) => Damage::Modified(runtime.home.join(path).to_string_lossy().into_owned()),
+                })),
+                Err(e) => warn!("CANNOT VERIFY RUNTIME {}: {}", runtime.id, e),
+            }
+        }
+        Ok(damages)
+    }
+    /// Writes the metadata of the version in its home
+    pub fn save(&self) -> Result<(), errors::WritingError> {
+        let path = format!("{}/{}/{}", mconf::config().versions, self.version, META_FILE);
+        self.mkmeta(&path)
+    }
     /// Crea un archivo establecido en `dir` y escribe el contenido de `map`
-    fn mkmeta(self, dir: &str) -> Result<(), errors::WritingError> {
+    fn mkmeta(&self, dir: &str) -> Result<(), errors::WritingError> {
         trace!("CALL TO MKMETA, CREATING META FILE ON {}", dir);
         trace!("DESERIALIZING VERSION");
-        let deserialize = toml::to_string(&self)?;
+        let deserialize = toml::to_string(self)?;
         trace!("VERSION DESERIALIZED ON {}", deserialize);
         trace!("WRITING FILE");
-        fs::write(dir, deserialize)?;
+        fs::write(dir, deserialize).map_err(|e| errors::WritingError::ReadError(dir.to_owned(), e))?;
         trace!("FILE SUCCESSFUL WRITED");
         Ok(())
     }
 }
-pub fn download(client: &Client, assets: bool) -> Result<(), DownloadError> {
+
+/// A running version, returned by [`Version::launch`]
+pub struct LaunchHandle<'a> {
+    version: String,
+    started: SystemTime,
+    pid: u32,
+    /// Shared with the [`Killer`]s, locked by [`LaunchHandle::wait`] only once the output ends
+    child: Arc<Mutex<Child>>,
+    receiver: Receiver<OutputLine>,
+    pumps: Vec<JoinHandle<()>>,
+    subscribers: Vec<(Option<Stream>, OutputCallback<'a>)>,
+}
+impl<'a> LaunchHandle<'a> {
+    /// Process id of the game
+    pub fn pid(&self) -> u32 {
+        self.pid
+    }
+    /// Subscribes `callback` to the lines written on stdout
+    pub fn on_stdout(&mut self, callback: OutputCallback<'a>) {
+        self.subscribers.push((Some(Stream::Stdout), callback));
+    }
+    /// Subscribes `callback` to the lines written on stderr
+    pub fn on_stderr(&mut self, callback: OutputCallback<'a>) {
+        self.subscribers.push((Some(Stream::Stderr), callback));
+    }
+    /// Subscribes `callback` to every line, whatever the stream
+    pub fn on_output(&mut self, callback: OutputCallback<'a>) {
+        self.subscribers.push((None, callback));
+    }
+    /// Kills the game from a subscriber or another thread while [`LaunchHandle::wait`] delivers
+    /// the output, the lines already read are still delivered
+    pub fn killer(&self) -> Killer {
+        Killer {
+            version: self.version.clone(),
+            child: self.child.clone(),
+        }
+    }
+    /// Delivers the output to the subscribers until the game exits and returns its exit code
+    pub fn wait(mut self) -> Result<i32, CommandError> {
+        // the receiver is exhausted once both pumps drop their senders
+        for line in self.receiver.iter() {
+            trace!("{:?} {:?}: {}", line.time, line.stream, line.line);
+            for (stream, callback) in self.subscribers.iter_mut() {
+                if stream.is_none_or(|stream| stream == line.stream) {
+                    callback(line.clone());
+                }
+            }
+        }
+        for pump in self.pumps.drain(..) {
+            pump.join().ok();
+        }
+        let status = self.child.lock().map_err(|_| io::Error::other("game process lost"))?.wait()?;
+        trace!("VERSION {} EXITED WITH {}", self.version, status);
+        mproc::unregister(&self.version, self.pid);
+        record_session(&self.version, self.started, self.started.elapsed().unwrap_or_default());
+        Ok(exit_code(status))
+    }
+}
+/// Kills a game launched with [`Version::launch`], see [`LaunchHandle::killer`]
+#[derive(Clone)]
+pub struct Killer {
+    version: String,
+    child: Arc<Mutex<Child>>,
+}
+impl Killer {
+    pub fn kill(&self) -> Result<(), CommandError> {
+        let mut child = self.child.lock().map_err(|_| io::Error::other("game process lost"))?;
+        trace!("KILLING VERSION {} ({})", self.version, child.id());
+        child.kill()?;
+        Ok(())
+    }
+}
+/// Records in the metadata of `version` a session started at `started` that lasted `played`
+pub fn record_session(version: &str, started: SystemTime, played: Duration) {
+    trace!("RECORDING SESSION OF {}: {:?}", version, played);
+    let started = started.duration_since(UNIX_EPOCH).map(|time| time.as_secs()).unwrap_or_default();
+    let result = get(version.to_owned()).and_then(|mut vers| {
+        vers.last_played = Some(vers.last_played.unwrap_or_default().max(started));
+        vers.playtime += played.as_secs();
+        Ok(vers.save()?)
+    });
+    if let Err(e) = result {
+        warn!("CANNOT RECORD SESSION OF {}: {}", version, e);
+    }
+}
+/// Whether an argument of `arguments` takes `placeholder`
+fn declares(arguments: &[ArgumentValue], placeholder: &str) -> bool {
+    arguments.iter().any(|argument| match argument {
+        ArgumentValue::Plain(value) => value.contains(placeholder),
+        ArgumentValue::Complex(argument) => match &argument.value {
+            ValueField::Single(value) => value.contains(placeholder),
+            ValueField::Multiple(values) => values.iter().any(|value| value.contains(placeholder)),
+        },
+    })
+}
+/// Exit code of a finished game, signals are reported as `128 + signal` like shells do
+fn exit_code(status: ExitStatus) -> i32 {
+    #[cfg(unix)]
+    if let Some(signal) = std::os::unix::process::ExitStatusExt::signal(&status) {
+        return 128 + signal;
+    }
+    status.code().unwrap_or(1)
+}
+/// Installs `client`, whose JSON is in `json`, reporting the progress to `on_event`
+pub fn download(client: &Client, json: &Path, assets: bool, on_event: DownloadCallback) -> Result<(), DownloadError> {
+    let mut plan = plan(client, json, assets)?;
+    let files = std::mem::take(&mut plan.files);
+    let failed = queue(vec![client.id.clone()], files, &on_event)?;
+    if !failed.is_empty() {
+        return Err(DownloadError::Incomplete(failed.len()));
+    }
+    install(plan, &on_event)
+}
+/// Installs every client of `clients` with a single download, the files shared by several
+/// versions, as the asset objects or the Java runtimes, are downloaded once. Returns the result of
+/// each version, in the order of `clients`
+pub fn download_all(
+    clients: &[(Client, PathBuf)],
+    assets: bool,
+    on_event: DownloadCallback,
+) -> Vec<(String, Result<(), DownloadError>)> {
+    let mut results: Vec<Option<Result<(), DownloadError>>> = clients.iter().map(|_| None).collect();
+    let mut plans = Vec::new();
+    for (i, (client, json)) in clients.iter().enumerate() {
+        match plan(client, json, assets) {
+            Ok(plan) => plans.push((i, plan)),
+            Err(e) => {
+                on_event(DownloadEvent::Failed { version: client.id.clone(), error: errors::chain(&e) });
+                results[i] = Some(Err(e));
+            }
+        }
+    }
+    // the files of each version, to fail the versions owning the files that are not downloaded
+    let owned: Vec<HashSet<String>> = plans
+        .iter()
+        .map(|(_, plan)| plan.files.iter().map(|(_, file)| file.path.clone()).collect())
+        .collect();
+    let mut seen = HashSet::new();
+    let files = plans
+        .iter_mut()
+        .flat_map(|(_, plan)| std::mem::take(&mut plan.files))
+        .filter(|(_, file)| seen.insert(file.path.clone()))
+        .collect();
+    let ids: Vec<String> = plans.iter().map(|(_, plan)| plan.client.id.clone()).collect();
+    trace!("DOWNLOADING {} FILES OF {:?}", seen.len(), ids);
+    let downloaded = if plans.is_empty() { Ok(HashSet::new()) } else { queue(ids, files, &on_event) };
+    for ((i, plan), owned) in plans.into_iter().zip(owned) {
+        let version = plan.client.id.clone();
+        let result = match &downloaded {
+            Ok(failed) => match owned.intersection(failed).count() {
+                0 => install(plan, &on_event),
+                missing => Err(DownloadError::Incomplete(missing)),
+            },
+            // the error of the download is shared by all the versions
+            Err(e) => Err(DownloadError::Files(io::Error::other(
+                std::error::Error::source(e).map_or_else(|| e.to_string(), |source| source.to_string()),
+            ))),
+        };
+        if let Err(e) = &result {
+            on_event(DownloadEvent::Failed { version, error: errors::chain(e) });
+        }
+        results[i] = Some(result);
+    }
+    clients
+        .iter()
+        .zip(results)
+        .map(|((client, _), result)| (client.id.clone(), result.unwrap_or(Ok(()))))
+        .collect()
+}
+
+/// Files of a version to download and what [`install`] needs after
+struct Plan<'a> {
+    client: &'a Client,
+    json: &'a Path,
+    files: Vec<(Category, DLFile)>,
+    java_fetched: bool,
+    classpath: Vec<String>,
+    logging_arg: Option<String>,
+}
+fn home_of(client: &Client) -> String {
+    format!("{}/{}", mconf::config().versions, client.id)
+}
+/// Resolves the files of `client`, nothing is downloaded but the assets index
+fn plan<'a>(client: &'a Client, json: &'a Path, assets: bool) -> Result<Plan<'a>, DownloadError> {
     // Crear utilitarios
     trace!("CALL TO DOWNLOAD, ASSETS: {}", assets);
     trace!("INITIALIZING UTILITIES");
-    let javau = JavaUtil::new();
     let libsu = LibsUtil::new();
     let resu = ResourceUtil::new();
 
     // definir paths
     trace!("DEFINING PATHS");
-    let home = format!("{}/{}", mconf::get::<String>("versions"), client.id);
+    let home = home_of(client);
     let jar_path = format!("{}/{}.jar", home, client.id);
     let libs_path = format!("{}/libraries", home);
-    let resource_path = mconf::get::<String>("resources");
+    let resource_path = &mconf::config().resources;
     let index_path = format!("{}/indexes/{}.json", resource_path, client.assets);
-    let java_home = mconf::get::<String>("java");
+    let java_home = &mconf::config().java;
     let natives_path = format!("{}/bin", home);
-    let info_path = format!("{}/.info", home);
-    trace!("HOME: {}\n\tJAR: {}\n\tLIBS: {}\n\tRESOURCES: {}\n\tINDEX: {}\n\tJAVA: {}\n\tNATIVES: {}\n\tINFO: {}",
-        home, jar_path, libs_path, resource_path, index_path, java_home, natives_path, info_path);
+    trace!("HOME: {}\n\tJAR: {}\n\tLIBS: {}\n\tRESOURCES: {}\n\tINDEX: {}\n\tJAVA: {}\n\tNATIVES: {}",
+        home, jar_path, libs_path, resource_path, index_path, java_home, natives_path);
     // crear cola de descarga
     let mut files = Vec::new();
     // anyadir el cliente
     trace!("FETCH ON CLIENT");
-    match fetch_client(&client, &jar_path) {
-        Ok(file) => files.push(file),
+    match fetch_client(client, &jar_path) {
+        Ok(file) => files.push((Category::Client, file)),
         Err(e) => warn!("WARN --- {}", e),
     }
     // anyadir la version de java
     trace!("FETCH ON JAVA");
-    match javau.fetch(client.java(), &java_home) {
-        Ok(file) => files.push(file),
-        Err(e) => warn!("WARN --- {}", e),
-    }
+    let java_fetched = match mjava::fetch(client.java()) {
+        Ok(file) => {
+            files.extend(file.map(|file| (Category::Java, file)));
+            true
+        }
+        Err(e) => {
+            warn!("WARN --- {}", e);
+            false
+        }
+    };
     // anyadir las librerias
     trace!("FETCH ON LIBS");
-    let mut classpath = match libsu.fetch(&libs_path, &natives_path, &client) {
-        Ok((mut file, classpath)) => { files.append(&mut file); classpath },
+    let mut classpath = match libsu.fetch(&libs_path, &natives_path, client) {
+        Ok((file, classpath)) => {
+            // the natives are the classifiers, out of the classpath
+            files.extend(file.into_iter().map(|file| {
+                let category = if classpath.contains(&file.path) { Category::Libraries } else { Category::Natives };
+                (category, file)
+            }));
+            classpath
+        }
         Err(e) => { warn!("WARN --- {}", e); Vec::new() },
     };
     // anyadir el cliente al classpath
     classpath.push(jar_path);
+    // anyadir la configuracion de log4j del cliente
+    let logging = client.logging.as_ref().map(|logging| &logging.client).filter(|logging| !logging.file.url.is_empty());
+    let logging_arg = logging.map(|logging| {
+        trace!("FETCH ON LOGGING CONFIG");
+        let path = format!("{}/log_configs/{}", resource_path, logging.file.id);
+        files.push((
+            Category::Client,
+            DLFile::new()
+                .with_url(&logging.file.url)
+                .with_path(&path)
+                .with_size(logging.file.size)
+                .with_hashes(DLHashes::new().sha1(&logging.file.sha1)),
+        ));
+        logging.argument.replace("${path}", &path)
+    });
     // anyadir librerias si se pide
     if assets {
         trace!("FETCH ON ASSETS");
-        let index = resu.index_of(&client, &index_path)?;
-        match resu.fetch(&index, &resource_path) {
-            Ok(mut file) => files.append(&mut file),
+        let index = resu.index_of(client, &index_path)?;
+        match resu.fetch(&index, resource_path) {
+            Ok(file) => files.extend(file.into_iter().map(|file| (Category::Assets, file))),
             Err(e) => warn!("WARN --- {}", e),
         }
     }
+    Ok(Plan { client, json, files, java_fetched, classpath, logging_arg })
+}
+/// Downloads `files` of `versions` with a single [`Downloader`], reporting the progress to `on_event`.
+/// Returns the paths that were not downloaded, dwldutil drops the errors of each file
+fn queue(
+    versions: Vec<String>,
+    files: Vec<(Category, DLFile)>,
+    on_event: &DownloadCallback,
+) -> Result<HashSet<String>, DownloadError> {
+    let mut categories = BTreeMap::new();
+    for (category, file) in &files {
+        *categories.entry(*category).or_insert(0) += file.size;
+    }
+    on_event(DownloadEvent::Start {
+        versions,
+        files: files.len(),
+        size: categories.values().sum(),
+        categories,
+    });
+    let mut failed: HashSet<String> = files.iter().map(|(_, file)| file.path.clone()).collect();
+    let downloaded = Arc::new(Mutex::new(HashSet::new()));
+    let files = files
+        .into_iter()
+        .map(|(category, file)| {
+            let on_event = on_event.clone();
+            let downloaded = downloaded.clone();
+            let size = file.size;
+            file.with_on_download(Arc::new(move |path| {
+                if let Ok(mut downloaded) = downloaded.lock() {
+                    downloaded.insert(path.clone());
+                }
+                on_event(DownloadEvent::File { path, category, size })
+            }))
+        })
+        .collect();
     // descargar todo
     info!("DOWNLOADING...");
-    Downloader::new().with_files(files).with_max_concurrent_downloads(mconf::get("max_current_downloads")).start();
+    // the progress is reported with the events, the bars of each file are hidden
+    start(
+        Downloader::new()
+            .with_files(files)
+            .with_max_concurrent_downloads(mconf::config().max_current_downloads)
+            .with_style(mprogress::hidden()),
+    )?;
+    if let Ok(downloaded) = downloaded.lock() {
+        failed.retain(|path| !downloaded.contains(path));
+    }
+    if !failed.is_empty() {
+        warn!("{} FILES NOT DOWNLOADED: {:?}", failed.len(), failed);
+    }
+    Ok(failed)
+}
+/// Installs the version of `plan` once its files are downloaded
+fn install(plan: Plan, on_event: &DownloadCallback) -> Result<(), DownloadError> {
+    let Plan { client, json, java_fetched, classpath, logging_arg, .. } = plan;
+    let javau = JavaUtil::new();
+    let home = home_of(client);
+    let natives_path = format!("{}/bin", home);
+    let libs_path = format!("{}/libraries", home);
+    let info_path = format!("{}/.info", home);
+    let json_path = format!("{}/{}.json", home, client.id);
+    if java_fetched {
+        mjava::install(client.java())?;
+    }
     // obtener argumentos
     trace!("BUILDING ARGS");
-    let (game, jvm) = build_args(&client, mconf::get("options"));
+    let (game, mut jvm) = build_args(client, mconf::config().options.clone());
+    // the logging argument must be passed before the main class
+    if let Some(arg) = logging_arg {
+        let main = jvm.iter().position(|arg| arg == "${main_class}").unwrap_or(jvm.len());
+        jvm.insert(main, arg);
+    }
+    // a download over an installed version keeps its history and launch defaults
+    let previous = if Path::new(&info_path).exists() {
+        Version::from_path(Path::new(&home))
+            .inspect_err(|e| warn!("CANNOT READ THE PREVIOUS METADATA OF {}: {}", client.id, e))
+            .ok()
+    } else {
+        None
+    };
     // registrar la version
-    let version = Version {
-        pwd: mconf::get("pwd"),
+    let mut version = Version {
+        pwd: mconf::config().pwd.clone(),
         version: client.id.clone(),
         assets: client.assets.clone(),
         main: client.main_class.clone(),
-        java: format!("{}/{}/bin/{}", mconf::get::<String>("java"), javau.id_of(client.java()).unwrap(), JAVA_BIN),
+        java: format!("{}/{}/bin/{}", mconf::config().java, javau.id_of(client.java()).ok_or_else(|| FetchError::UrlNotFound(client.java().to_string()))?, JAVA_BIN),
         jvm_args: jvm,
         game_args: game,
         data: HashMap::new(),
@@ -183,11 +866,25 @@ pub fn download(client: &Client, assets: bool) -> Result<(), DownloadError> {
         classpath: classpath.join(CP_SEPARATOR.to_string().as_str()),
         natives: natives_path,
         libraries: libs_path,
-        java_version: client.java()
+        java_version: client.java(),
+        last_played: None,
+        playtime: 0,
+        launch: LaunchOptions::default(),
     };
+    if let Some(previous) = previous {
+        trace!("KEEPING THE HISTORY AND LAUNCH DEFAULTS OF {}", client.id);
+        version.data = previous.data;
+        version.last_played = previous.last_played;
+        version.playtime = previous.playtime;
+        version.launch = previous.launch;
+    }
     // escribir el archivo de metadatos
     trace!("WRITING METADATA");
     version.mkmeta(&info_path)?;
+    // the client JSON is kept to evaluate its rule-gated arguments on launch
+    trace!("COPYING CLIENT JSON TO {}", json_path);
+    fs::copy(json, &json_path)?;
+    on_event(DownloadEvent::Done { version: client.id.clone(), home });
     Ok(())
 }
 
@@ -195,17 +892,17 @@ pub fn download(client: &Client, assets: bool) -> Result<(), DownloadError> {
 pub fn list() -> Result<HashMap<String, Version>, errors::ReadingError> {
     trace!("CALL TO LIST");
     // define el dir e inicializa el map
-    let dir: String = mconf::get("versions");
+    let dir: String = mconf::config().versions.clone();
     trace!("REAIDNG VERSIONS DIR: {}", dir);
     let dir = Path::new(dir.as_str());
     let mut map: HashMap<String, Version> = HashMap::new();
+    let read_error = |e| ReadingError::ReadError(dir.to_path_buf(), e);
     // por cada directorio existente en dir, si contiene un .info, lo anyade al map
-    for entry in fs::read_dir(dir)? {
-        let entry = entry?;
+    for entry in fs::read_dir(dir).map_err(read_error)? {
+        let entry = entry.map_err(read_error)?;
         trace!("FOUND ENTRY ON DIR: {:?}", entry);
         let path = entry.path();
-        let meta = path.metadata()?;
-        if meta.is_dir() {
+        if path.is_dir() {
             trace!("LOADING DIR: {:?}", entry);
             let version = Version::from_path(&path)?;
             map.insert(version.version.clone(), version);
@@ -215,60 +912,218 @@ pub fn list() -> Result<HashMap<String, Version>, errors::ReadingError> {
 }
 
 /// Obtiene una version en concreto
-pub fn get(version: String) -> Option<Version> {
+pub fn get(version: String) -> Result<Version, McwrError> {
     trace!("CALL GET {}", version);
-    let list = list().unwrap_or(HashMap::new());
-    if list.get(&version).is_none() {
+    let home = Path::new(&mconf::config().versions).join(&version);
+    if !home.join(META_FILE).exists() {
         warn!("VERSION NOT FOUND");
-        return None;
-    } else {
-        Some(list.get(&version).unwrap().clone())
+        return Err(McwrError::NotInstalled(version));
     }
+    Ok(Version::from_path(&home)?)
 }
 /// elimina una version
-pub fn remove(version: String) {
+pub fn remove(version: String) -> Result<(), McwrError> {
     trace!("CALL TO REMOVE {}", version);
-    let path = format!("{}/{}", mconf::get::<String>("versions"), version);
+    let path = format!("{}/{}", mconf::config().versions, version);
     trace!("ON PATH {}", path);
     let version_path = Path::new(path.as_str());
-    fs::remove_dir_all(version_path).ok();
+    if !version_path.exists() {
+        return Err(McwrError::NotInstalled(version));
+    }
+    fs::remove_dir_all(version_path)?;
+    Ok(())
+}
+
+/// Copies the home of `version` as the new version `to`, without its logs and playtime
+pub fn clone(version: &str, to: &str) -> Result<Version, McwrError> {
+    trace!("CALL TO CLONE {} TO {}", version, to);
+    relocate(version, to, false)
+}
+/// Renames `version` to `to`
+pub fn rename(version: &str, to: &str) -> Result<Version, McwrError> {
+    trace!("CALL TO RENAME {} TO {}", version, to);
+    if mproc::list()?.iter().any(|state| state.version == version) {
+        return Err(McwrError::Running(version.to_owned()));
+    }
+    relocate(version, to, true)
+}
+/// Copies or moves the home of `version` to the home of `to`, renaming its client jar and JSON
+/// and rewriting the paths of its metadata
+fn relocate(version: &str, to: &str, keep: bool) -> Result<Version, McwrError> {
+    if to.is_empty() || to == "." || to == ".." || to.contains(['/', '\\']) {
+        return Err(McwrError::InvalidName(to.to_owned()));
+    }
+    let mut vers = get(version.to_owned())?;
+    let versions = mdirs::get().data.join(&mconf::config().versions);
+    let (from, home) = (versions.join(version), versions.join(to));
+    if home.exists() {
+        return Err(McwrError::AlreadyInstalled(to.to_owned()));
+    }
+    if keep {
+        mdirs::move_path(&from, &home)?;
+        mlogs::rename(&home.join(mlogs::LOGS_DIR), version, to)?;
+    } else {
+        mdirs::copy_path(&from, &home)?;
+        // the sessions belong to the original version
+        for dir in [mproc::RUNNING_DIR, mlogs::LOGS_DIR] {
+            let dir = home.join(dir);
+            if dir.exists() {
+                fs::remove_dir_all(dir)?;
+            }
+        }
+        vers.last_played = None;
+        vers.playtime = 0;
+    }
+    let (old_jar, jar) = (home.join(format!("{}.jar", version)), home.join(format!("{}.jar", to)));
+    if old_jar.exists() {
+        fs::rename(&old_jar, &jar)?;
+    }
+    let json = home.join(format!("{}.json", to));
+    if let Ok(content) = fs::read_to_string(home.join(format!("{}.json", version))) {
+        let mut client: serde_json::Value = serde_json::from_str(&content).map_err(io::Error::from)?;
+        client["id"] = serde_json::Value::String(to.to_owned());
+        fs::write(&json, serde_json::to_string_pretty(&client).map_err(io::Error::from)?)?;
+        fs::remove_file(home.join(format!("{}.json", version)))?;
+    }
+    // the paths under the old home, relative ones are relative to the data directory
+    let moved = |path: &str| -> String {
+        let absolute = mdirs::get().data.join(path);
+        match absolute.strip_prefix(&from) {
+            Ok(rest) if rest == Path::new(&format!("{}.jar", version)) => jar.to_string_lossy().into_owned(),
+            Ok(rest) => home.join(rest).to_string_lossy().into_owned(),
+            Err(_) => path.to_owned(),
+        }
+    };
+    vers.classpath = vers.classpath.split(CP_SEPARATOR).map(moved).collect::<Vec<_>>().join(CP_SEPARATOR.to_string().as_str());
+    vers.natives = moved(&vers.natives);
+    vers.libraries = moved(&vers.libraries);
+    vers.version = to.to_owned();
+    vers.save()?;
+    Ok(vers)
 }
 
 /// lista todas las versiones del manifest
-pub fn list_manifest() -> Vec<String> {
+pub fn list_manifest() -> Result<Vec<String>, McwrError> {
     trace!("CALL TO LIST MANIFEST");
-    let manifest = manifest().unwrap();
-    manifest
+    Ok(manifest_entries()?.into_iter().map(|entry| entry.id).collect())
+}
+/// Versions of the manifest, newest first
+pub fn manifest_entries() -> Result<Vec<ManifestEntry>, McwrError> {
+    Ok(manifest()?
         .versions
-        .iter()
-        .map(|version| version.id.clone())
-        .collect()
+        .into_iter()
+        .map(|version| ManifestEntry {
+            id: version.id,
+            version_type: version.version_type,
+            url: version.url,
+            time: version.time,
+            release_time: version.release_time,
+        })
+        .collect())
 }
 /// get manifest latest
-pub fn manifest_latest() -> Latest {
-    let manifest = manifest().unwrap();
-    manifest.latest
+pub fn manifest_latest() -> Result<Latest, McwrError> {
+    Ok(manifest()?.latest)
 }
 /// get manifest latest release
-pub fn manifest_latest_release() -> String {
-    manifest_latest().release.clone()
+pub fn manifest_latest_release() -> Result<String, McwrError> {
+    Ok(manifest_latest()?.release)
 }
 /// get manifest latest snapshot
-pub fn manifest_latest_snapshot() -> String {
-    manifest_latest().snapshot.clone()
+pub fn manifest_latest_snapshot() -> Result<String, McwrError> {
+    Ok(manifest_latest()?.snapshot)
+}
+pub fn manifest() -> Result<Manifest, McwrError> {
+    Ok(api()?.manifest)
+}
+/// Client of the version manifest, downloaded if it is not cached yet
+pub fn api() -> Result<ApiClientUtil, McwrError> {
+    let path = &mconf::config().manifest;
+    unpanic(|| ApiClientUtil::new(path))
+        .map_err(ApiClientError::IOError)
+        .and_then(|api| api)
+        .map_err(|source| McwrError::Manifest { path: path.clone(), source })
+}
+/// Downloads the files of `downloader`
+pub fn start(downloader: Downloader) -> Result<(), DownloadError> {
+    // the downloader is dropped with the panic, nothing of it is used after
+    unpanic(AssertUnwindSafe(move || downloader.start())).map_err(DownloadError::Files)
+}
+/// Runs `f`, turning its panic into an error. dwldutil panics when a file cannot be downloaded.
+/// The panics of `f` are not printed, the ones of other threads are reported by the previous hook
+fn unpanic<T>(f: impl FnOnce() -> T + UnwindSafe) -> io::Result<T> {
+    static HOOK: Once = Once::new();
+    HOOK.call_once(|| {
+        let hook = panic::take_hook();
+        panic::set_hook(Box::new(move |info| {
+            if !UNPANIC.get() {
+                hook(info);
+            }
+        }));
+    });
+    UNPANIC.set(true);
+    let result = panic::catch_unwind(f);
+    UNPANIC.set(false);
+    result.map_err(|panic| {
+        let message = panic
+            .downcast_ref::<String>()
+            .cloned()
+            .or_else(|| panic.downcast_ref::<&str>().map(|message| message.to_string()))
+            .unwrap_or_else(|| String::from("download failed"));
+        // the message may carry the backtrace of the error
+        io::Error::other(message.lines().next().unwrap_or_default().to_owned())
+    })
 }
-pub fn manifest() -> Result<Manifest, ApiClientError> {
-    Ok(ApiClientUtil::new(&mconf::get::<String>("manifest"))?.manifest)
+/// Writes the client JSON of `version` in `path`. `version` may be the path of a local client
+/// JSON as well, it is written merged with the versions it inherits from
+pub fn fetch(version: &str, path: &str) -> Result<Client, McwrError> {
+    let client = if Path::new(version).is_file() {
+        let client = minherit::resolve(Path::new(version))?;
+        fs::write(path, client.to_string())?;
+        serde_json::from_value(client).map_err(ApiClientError::from)
+    } else {
+        api()?.fetch(version, path)
+    };
+    client.map_err(|source| match source {
+        ApiClientError::VersionNotExist(version) => McwrError::UnknownVersion(version),
+        source => McwrError::Client { version: version.to_owned(), source },
+    })
+}
+/// Reads `reader` line by line on its own thread, sending every line through `sender`
+fn pump<R: Read + Send + 'static>(reader: R, stream: Stream, sender: Sender<OutputLine>) -> JoinHandle<()> {
+    thread::spawn(move || {
+        let mut reader = BufReader::new(reader);
+        let mut parser = Log4jParser::default();
+        let mut buf = Vec::new();
+        loop {
+            buf.clear();
+            match reader.read_until(b'\n', &mut buf) {
+                Ok(0) | Err(_) => break,
+                Ok(_) => {
+                    let line = String::from_utf8_lossy(&buf).trim_end_matches(['\r', '\n']).to_owned();
+                    let (line, record) = match parser.feed(line) {
+                        Parsed::Line(line) => (line, None),
+                        Parsed::Record(record) => (record.to_string(), Some(record)),
+                        Parsed::Pending => continue,
+                    };
+                    let line = OutputLine { stream, time: SystemTime::now(), line, record };
+                    if sender.send(line).is_err() {
+                        break;
+                    }
+                }
+            }
+        }
+    })
 }
 fn data() -> HashMap<String, String> {
-    let mut data: HashMap<String, String> = mconf::get::<HashMap<String, String>>("data");
-    data.insert("auth_uuid".to_owned(), mconf::get_or("uuid", String::from("000")));
-    data.insert("auth_access_token".to_owned(), mconf::get_or("token", String::from("000")));
-    data.insert("clientid".to_owned(), mconf::get_or("clientid", String::from("000")));
-    data.insert("auth_xuid".to_owned(), mconf::get_or("xuid", String::from("000")));
-    data.insert("user_type".to_owned(), mconf::get_or("usertype", String::from("msa")));
+    let mut data: HashMap<String, String> = mconf::config().data.clone();
+    data.insert("auth_uuid".to_owned(), mconf::config().uuid.clone());
+    data.insert("auth_access_token".to_owned(), mconf::config().token.clone());
+    data.insert("clientid".to_owned(), mconf::config().clientid.clone());
+    data.insert("auth_xuid".to_owned(), mconf::config().xuid.clone());
+    data.insert("user_type".to_owned(), mconf::config().usertype.clone());
     data.insert("classpath_separator".to_owned(), CP_SEPARATOR.to_string());
-    data.insert("assets_root".to_owned(), mconf::get("resources"));
-    data.insert("game_assets".to_owned(), mconf::get("resources"));
+    data.insert("assets_root".to_owned(), mconf::config().resources.clone());
+    data.insert("game_assets".to_owned(), mconf::config().resources.clone());
     data
 }